@@ -0,0 +1,126 @@
+use std::io::{self, Read};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use common::outdated::OutdatedPackage;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InputFormat {
+    /// Guess the format from the input
+    Auto,
+    /// `pnpm outdated --format json`
+    Pnpm,
+    /// `npm outdated --json`
+    Npm,
+    /// `yarn outdated --json`
+    Yarn,
+}
+
+pub fn parse_input(format: InputFormat) -> Result<Vec<OutdatedPackage>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    parse(&input, format)
+}
+
+pub fn parse(input: &str, format: InputFormat) -> Result<Vec<OutdatedPackage>> {
+    let format = match format {
+        InputFormat::Auto => detect(input),
+        format => format,
+    };
+
+    match format {
+        InputFormat::Npm => deputui_npm::parse(input),
+        InputFormat::Yarn => deputui_yarn::parse(input),
+        _ => deputui_pnpm::pnpm::parse(input),
+    }
+}
+
+/// Yarn prints an array (berry) or one event per line (classic). npm lists a package per workspace in an array, and tells where it is installed via `location` and `dependent`. pnpm does neither.
+fn detect(input: &str) -> InputFormat {
+    let packages = match serde_json::from_str::<Value>(input) {
+        Ok(Value::Array(_)) => return InputFormat::Yarn,
+        Ok(Value::Object(object)) if is_yarn_event(&object) => return InputFormat::Yarn,
+        Ok(Value::Object(packages)) => packages,
+        Ok(_) => return InputFormat::Pnpm,
+        Err(_) => {
+            let first_line = input.lines().next().unwrap_or_default();
+            return match serde_json::from_str::<Value>(first_line) {
+                Ok(Value::Object(object)) if is_yarn_event(&object) => InputFormat::Yarn,
+                _ => InputFormat::Pnpm,
+            };
+        }
+    };
+
+    let is_npm = packages.values().any(|package| match package {
+        Value::Array(_) => true,
+        Value::Object(fields) => {
+            fields.contains_key("location") || fields.contains_key("dependent")
+        }
+        _ => false,
+    });
+
+    match is_npm {
+        true => InputFormat::Npm,
+        false => InputFormat::Pnpm,
+    }
+}
+
+fn is_yarn_event(object: &serde_json::Map<String, Value>) -> bool {
+    matches!(object.get("type"), Some(Value::String(_))) && object.contains_key("data")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_pnpm() {
+        let input = r#"{
+            "foo": {
+                "current": "1.0.0",
+                "latest": "1.1.0",
+                "wanted": "1.0.0",
+                "isDeprecated": false,
+                "dependencyType": "dependencies"
+            }
+        }"#;
+        assert_eq!(detect(input), InputFormat::Pnpm);
+    }
+
+    #[test]
+    fn test_detect_npm() {
+        let input = r#"{
+            "foo": {
+                "current": "1.0.0",
+                "wanted": "1.0.0",
+                "latest": "1.1.0",
+                "dependent": "my-app",
+                "location": "/home/me/my-app/node_modules/foo"
+            }
+        }"#;
+        assert_eq!(detect(input), InputFormat::Npm);
+    }
+
+    #[test]
+    fn test_detect_npm_workspaces() {
+        let input = r#"{ "foo": [{ "current": "1.0.0", "wanted": "1.0.0", "latest": "1.1.0" }] }"#;
+        assert_eq!(detect(input), InputFormat::Npm);
+    }
+
+    #[test]
+    fn test_detect_yarn_classic() {
+        let input = concat!(
+            r#"{"type":"info","data":"Color legend: ..."}"#,
+            "\n",
+            r#"{"type":"table","data":{"head":["Package","Current","Wanted","Latest","Package Type","URL"],"body":[]}}"#,
+        );
+        assert_eq!(detect(input), InputFormat::Yarn);
+    }
+
+    #[test]
+    fn test_detect_yarn_berry() {
+        let input = r#"[{ "name": "foo", "current": "1.0.0", "latest": "1.1.0" }]"#;
+        assert_eq!(detect(input), InputFormat::Yarn);
+    }
+}
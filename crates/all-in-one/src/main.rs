@@ -1,23 +1,78 @@
+use std::io::{self, IsTerminal};
+
 use anyhow::Result;
 use clap::Parser;
-use deputui_pnpm::{fetch_all_releases, parse_input};
+use common::{
+    npmrc,
+    project::{self, OutdatedDependencies},
+    release::{PackageError, ReleaseEntry},
+};
+use deputui_pnpm::fetch_all_releases_with;
 use deputui_review::run_review_tui;
 
 mod args;
+mod input;
 
-use crate::args::Args;
+use crate::{args::Args, input::parse_input};
 
 fn main() -> Result<()> {
-    Args::parse();
+    let args = Args::parse();
+    args.http.init();
+
+    let project = match (args.project, io::stdin().is_terminal()) {
+        (Some(path), _) => Some(path),
+        (None, true) => Some(".".into()),
+        (None, false) => None,
+    };
+
+    let selected_releases = smol::block_on(async {
+        let (npmrc, outdated) = match project {
+            Some(path) => {
+                let npmrc = npmrc::init(&path);
+                let outdated =
+                    project::outdated_packages(npmrc, &path, args.fetch.concurrency).await?;
+                (npmrc, outdated)
+            }
+            None => (
+                npmrc::get(),
+                OutdatedDependencies {
+                    packages: parse_input(args.input_format)?,
+                    ..Default::default()
+                },
+            ),
+        };
+        let filter = args.fetch.release_filter();
+        filter.check_dependency_types(&outdated.packages)?;
+        let entries = fetch_all_releases_with(
+            npmrc,
+            outdated.packages,
+            outdated.npm_packages,
+            &filter,
+            args.fetch.concurrency,
+        )
+        .await;
+        let entries: Vec<ReleaseEntry> = outdated
+            .errors
+            .into_iter()
+            .map(ReleaseEntry::Error)
+            .chain(entries)
+            .collect();
+        let errors: Vec<PackageError> = entries
+            .iter()
+            .filter_map(ReleaseEntry::as_error)
+            .cloned()
+            .collect();
+        let selected_releases = run_review_tui(entries).await;
 
-    let parsed = parse_input()?;
+        // The TUI has cleared the screen, so report failures once it's gone
+        for error in errors {
+            eprintln!("Failed to fetch releases of {}", error);
+        }
 
-    let selected_packages = smol::block_on(async {
-        let releases = fetch_all_releases(parsed).await?;
-        run_review_tui(releases).await
+        selected_releases
     })?;
 
-    println!("{}", selected_packages.join(" "));
+    println!("{}", args.output.format(&selected_releases));
 
     Ok(())
 }
@@ -1,42 +1,299 @@
 //! An HTTP+TLS client based on `async-h1` and `async-native-tls`.
 
-use std::net::{TcpStream, ToSocketAddrs};
+use std::{
+    env,
+    io::{self, Read as _},
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, SystemTime},
+};
 
-use anyhow::{bail, Context as _, Error, Result};
-use http_types::{Method, Request, Response};
-use smol::{prelude::*, Async};
+use anyhow::{bail, Context as _, Result};
+use flate2::read::GzDecoder;
+use http_types::{other::RetryAfter, Method, Request, Response, StatusCode};
+use smol::{prelude::*, Async, Timer};
 use url::Url;
 
-/// Sends a request and fetches the response.
+pub mod cache;
+mod pool;
+mod proxy;
+pub mod timeout;
+mod tls;
+
+use self::{
+    pool::Connection,
+    timeout::{Timeout, Timeouts},
+};
+
+/// How often and how patiently [`fetch_with_retries`] retries requests that failed for transient reasons.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled for every further retry
+    pub base_delay: Duration,
+    /// Caps both the exponential backoff and the server's `Retry-After`
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// How many redirects are followed before giving up, as browsers do.
+const MAX_REDIRECTS: usize = 10;
+
+/// Headers that authenticate requests, e.g. GitLab's `PRIVATE-TOKEN`. They must not leak to another origin when following a redirect, and responses to them are cached per credential.
+pub(crate) const CREDENTIAL_HEADERS: [&str; 3] = ["Authorization", "Cookie", "PRIVATE-TOKEN"];
+
+/// Sends a request and fetches the response, through the proxy that the environment configures for it.
+///
+/// Follows redirects, decompresses gzipped responses, reuses connections, and gives up after the configured [`timeout`]s. The body of the response has been read already.
 pub async fn fetch(req: Request) -> Result<Response> {
-    // Figure out the host and the port.
-    let host = req.url().host().context("cannot parse host")?.to_string();
-    let port = req
+    fetch_with_timeouts(req, timeout::get()).await
+}
+
+async fn fetch_with_timeouts(mut req: Request, timeouts: &Timeouts) -> Result<Response> {
+    if req.header("Accept-Encoding").is_none() {
+        req.insert_header("Accept-Encoding", "gzip");
+    }
+
+    let follow_redirects = async {
+        for _ in 0..=MAX_REDIRECTS {
+            let proxy = proxy::proxy_for(req.url(), |name| env::var(name).ok());
+            let next_req = req.clone();
+            let resp = fetch_via(req, proxy.as_ref(), timeouts).await?;
+
+            match redirect(next_req, &resp)? {
+                Some(next_req) => req = next_req,
+                None => return decompress(resp).await,
+            }
+        }
+
+        bail!("Too many redirects, more than {}", MAX_REDIRECTS)
+    };
+
+    follow_redirects
+        .or(async {
+            Timer::after(timeouts.total).await;
+            Err(Timeout::Total(timeouts.total).into())
+        })
+        .await
+}
+
+/// The request to send next if `resp` redirects `req`.
+fn redirect(mut req: Request, resp: &Response) -> Result<Option<Request>> {
+    if !matches!(resp.status().into(), 301 | 302 | 303 | 307 | 308) {
+        return Ok(None);
+    }
+    let Some(location) = resp.header("Location") else {
+        return Ok(None);
+    };
+
+    let url = req
         .url()
-        .port_or_known_default()
-        .context("cannot guess port")?;
+        .join(location.as_str())
+        .with_context(|| format!("invalid redirect location: {}", location))?;
+
+    if url.origin() != req.url().origin() {
+        for name in CREDENTIAL_HEADERS {
+            req.remove_header(name);
+        }
+    }
+
+    // 303 asks to fetch the result with GET, whatever the method was
+    if resp.status() == StatusCode::SeeOther {
+        req.set_method(Method::Get);
+    }
+
+    *req.url_mut() = url;
+    Ok(Some(req))
+}
+
+async fn decompress(mut resp: Response) -> Result<Response> {
+    let is_gzipped = resp
+        .header("Content-Encoding")
+        .is_some_and(|encoding| encoding.as_str().eq_ignore_ascii_case("gzip"));
+    if !is_gzipped {
+        return Ok(resp);
+    }
+
+    let compressed = resp.body_bytes().await.map_err(|e| e.into_inner())?;
+    let body = smol::unblock(move || {
+        let mut body = Vec::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut body)
+            .map(|_| body)
+    })
+    .await
+    .context("cannot decompress gzipped response")?;
+
+    resp.remove_header("Content-Encoding");
+    resp.remove_header("Content-Length");
+    resp.set_body(body);
+    Ok(resp)
+}
+
+/// Sends the request on an idle connection to its origin if there is one, or on a new one.
+async fn fetch_via(req: Request, proxy: Option<&Url>, timeouts: &Timeouts) -> Result<Response> {
+    let origin = match proxy {
+        Some(proxy) => format!("{} via {}", req.url().origin().ascii_serialization(), proxy),
+        None => req.url().origin().ascii_serialization(),
+    };
+
+    if let Some(connection) = pool::take(&origin) {
+        connection.set_read_timeout(timeouts.read);
+        match exchange(connection, req.clone(), &origin).await {
+            // The server may have closed the idle connection in the meantime, so retry on a new one
+            Err(e) if Timeout::cause_of(&e).is_none() => {}
+            result => return result,
+        }
+    }
+
+    let connection = open_connection(req.url(), proxy, timeouts.read)
+        .or(async {
+            Timer::after(timeouts.connect).await;
+            Err(Timeout::Connect(timeouts.connect).into())
+        })
+        .await?;
+    exchange(connection, req, &origin).await
+}
+
+/// Sends the request and reads the whole response, so that the connection can be reused if the server allows.
+async fn exchange(connection: Connection, req: Request, origin: &str) -> Result<Response> {
+    let mut resp = async_h1::connect(connection.clone(), req)
+        .await
+        .map_err(http_types::Error::into_inner)?;
+    let body = resp.body_bytes().await.map_err(|e| e.into_inner())?;
 
-    // Connect to the host.
+    // Without length, the body ends where the connection does
+    let has_length =
+        resp.header("Content-Length").is_some() || resp.header("Transfer-Encoding").is_some();
+    let closes = resp
+        .header("Connection")
+        .is_some_and(|connection| connection.as_str().eq_ignore_ascii_case("close"));
+    if has_length && !closes {
+        pool::put(origin, connection);
+    }
+
+    resp.set_body(body);
+    Ok(resp)
+}
+
+/// Connects to the host of `url`, or to the proxy and tunnels to the host from there.
+async fn open_connection(
+    url: &Url,
+    proxy: Option<&Url>,
+    read_timeout: Duration,
+) -> Result<Connection> {
+    // Figure out the host and the port.
+    let host = url.host().context("cannot parse host")?.to_string();
+    let port = url.port_or_known_default().context("cannot guess port")?;
+
+    let stream = match proxy {
+        None => connect(&host, port).await?,
+        Some(proxy) => {
+            let proxy_host = proxy.host().context("cannot parse proxy host")?.to_string();
+            let proxy_port = proxy
+                .port_or_known_default()
+                .context("cannot guess proxy port")?;
+            let mut stream = connect(&proxy_host, proxy_port).await?;
+            proxy::tunnel(&mut stream, proxy, &host, port).await?;
+            stream
+        }
+    };
+
+    let connection = match url.scheme() {
+        "http" => Connection::new(stream, read_timeout),
+        "https" => {
+            // In case of HTTPS, establish a secure TLS connection first.
+            let stream = tls::connector()?.connect(&host, stream).await?;
+            Connection::new(stream, read_timeout)
+        }
+        scheme => bail!("unsupported scheme: {}", scheme),
+    };
+    Ok(connection)
+}
+
+async fn connect(host: &str, port: u16) -> Result<Async<TcpStream>> {
     let socket_addr = {
-        let host = host.clone();
+        let host = host.to_string();
         smol::unblock(move || (host.as_str(), port).to_socket_addrs())
             .await?
             .next()
             .context("cannot resolve address")?
     };
-    let stream = Async::<TcpStream>::connect(socket_addr).await?;
+    Ok(Async::<TcpStream>::connect(socket_addr).await?)
+}
 
-    // Send the request and wait for the response.
-    let resp = match req.url().scheme() {
-        "http" => async_h1::connect(stream, req).await.map_err(Error::msg)?,
-        "https" => {
-            // In case of HTTPS, establish a secure TLS connection first.
-            let stream = async_native_tls::connect(&host, stream).await?;
-            async_h1::connect(stream, req).await.map_err(Error::msg)?
+/// Like [`fetch_with_retries`], but answers from and stores into the on-disk [`cache`].
+pub async fn fetch_cached(req: Request) -> Result<Response> {
+    cache::get().fetch(req, &RetryPolicy::default()).await
+}
+
+/// Like [`fetch`], but retries with exponential backoff on `429 Too Many Requests`, server errors and dropped connections, honoring `Retry-After`.
+///
+/// Only requests without body can be retried, as the body is consumed by the first attempt.
+pub async fn fetch_with_retries(req: Request, policy: &RetryPolicy) -> Result<Response> {
+    let mut attempt = 0;
+    loop {
+        let result = fetch(req.clone()).await;
+
+        let retry_after = match &result {
+            Ok(resp) if is_transient_status(resp.status()) => retry_after(resp),
+            Ok(_) => return result,
+            Err(e) if is_transient_error(e) => None,
+            Err(_) => return result,
+        };
+
+        if attempt >= policy.max_retries {
+            return result;
         }
-        scheme => bail!("unsupported scheme: {}", scheme),
-    };
-    Ok(resp)
+
+        let backoff = policy
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt));
+        Timer::after(retry_after.unwrap_or(backoff).min(policy.max_delay)).await;
+        attempt += 1;
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TooManyRequests || status.is_server_error()
+}
+
+fn is_transient_error(error: &anyhow::Error) -> bool {
+    if let Some(timeout) = Timeout::cause_of(error) {
+        // The whole request taking too long is likely to happen again
+        return !matches!(timeout, Timeout::Total(_));
+    }
+
+    error.chain().any(|cause| {
+        cause.downcast_ref::<io::Error>().is_some_and(|e| {
+            matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::TimedOut
+            )
+        })
+    })
+}
+
+/// The delay the server asks for, given in seconds or as a date.
+fn retry_after(resp: &Response) -> Option<Duration> {
+    let retry_after = RetryAfter::from_headers(resp).ok()??;
+    Some(
+        retry_after
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    )
 }
 
 pub async fn get(addr: &str) -> Result<String> {
@@ -51,3 +308,276 @@ pub async fn get(addr: &str) -> Result<String> {
     resp.read_to_end(&mut body).await?;
     Ok(String::from_utf8_lossy(&body).into_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestProxy, TestResponse, TestServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn fast_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    fn server_failing_times(failures: usize, failure: TestResponse) -> TestServer {
+        let attempts = AtomicUsize::new(0);
+        TestServer::start(move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+            n if n < failures => failure.clone(),
+            _ => TestResponse::new(200, "ok"),
+        })
+    }
+
+    fn fetch_status(server: &TestServer, policy: &RetryPolicy) -> u16 {
+        let req = Request::new(Method::Get, Url::parse(&server.url("/")).unwrap());
+        let resp = smol::block_on(fetch_with_retries(req, policy)).unwrap();
+        resp.status().into()
+    }
+
+    #[test]
+    fn test_retries_server_errors() {
+        let server = server_failing_times(2, TestResponse::new(503, ""));
+
+        assert_eq!(fetch_status(&server, &fast_policy(3)), 200);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_gives_up_after_max_retries() {
+        let server = server_failing_times(5, TestResponse::new(500, ""));
+
+        assert_eq!(fetch_status(&server, &fast_policy(2)), 500);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_does_not_retry_client_errors() {
+        let server = server_failing_times(1, TestResponse::new(404, ""));
+
+        assert_eq!(fetch_status(&server, &fast_policy(3)), 404);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_retries_rate_limits_after_the_requested_delay() {
+        let server = server_failing_times(
+            1,
+            TestResponse::new(429, "").with_header("Retry-After", "1"),
+        );
+        let policy = RetryPolicy {
+            max_delay: Duration::from_secs(5),
+            ..fast_policy(1)
+        };
+
+        let started = std::time::Instant::now();
+        assert_eq!(fetch_status(&server, &policy), 200);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_tunnels_through_proxies() {
+        let server = TestServer::start(|_| TestResponse::new(200, "ok"));
+        let proxy = TestProxy::start();
+        let proxy_url = Url::parse(&proxy.url()).unwrap();
+
+        let req = Request::new(Method::Get, Url::parse(&server.url("/foo")).unwrap());
+        let mut resp =
+            smol::block_on(fetch_via(req, Some(&proxy_url), &Timeouts::default())).unwrap();
+
+        assert_eq!(smol::block_on(resp.body_string()).unwrap(), "ok");
+        let connects = proxy.requests();
+        assert_eq!(connects.len(), 1);
+        assert_eq!(connects[0].method, "CONNECT");
+        assert_eq!(connects[0].target, server.addr().to_string());
+        assert_eq!(connects[0].header("proxy-authorization"), None);
+        assert_eq!(server.requests()[0].target, "/foo");
+    }
+
+    #[test]
+    fn test_authenticates_with_proxies() {
+        let server = TestServer::start(|_| TestResponse::new(200, "ok"));
+        let proxy = TestProxy::start();
+        let mut proxy_url = Url::parse(&proxy.url()).unwrap();
+        proxy_url.set_username("alice").unwrap();
+        proxy_url.set_password(Some("s3cret")).unwrap();
+
+        let req = Request::new(Method::Get, Url::parse(&server.url("/")).unwrap());
+        smol::block_on(fetch_via(req, Some(&proxy_url), &Timeouts::default())).unwrap();
+
+        // base64 of `alice:s3cret`
+        assert_eq!(
+            proxy.requests()[0].header("proxy-authorization"),
+            Some("Basic YWxpY2U6czNjcmV0")
+        );
+    }
+
+    #[test]
+    fn test_follows_redirects() {
+        let server = TestServer::start(|request| match request.target.as_str() {
+            "/old" => TestResponse::new(301, "").with_header("Location", "/older"),
+            "/older" => TestResponse::new(307, "").with_header("Location", "/new"),
+            _ => TestResponse::new(200, "moved"),
+        });
+
+        let mut req = Request::new(Method::Get, Url::parse(&server.url("/old")).unwrap());
+        req.insert_header("Authorization", "Bearer secret");
+        let mut resp = smol::block_on(fetch(req)).unwrap();
+
+        assert_eq!(smol::block_on(resp.body_string()).unwrap(), "moved");
+        let requests = server.requests();
+        let targets: Vec<&str> = requests.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(targets, ["/old", "/older", "/new"]);
+        // Same origin, so the credentials are still needed
+        assert_eq!(requests[2].header("authorization"), Some("Bearer secret"));
+    }
+
+    #[test]
+    fn test_strips_credentials_on_cross_origin_redirects() {
+        let target = TestServer::start(|_| TestResponse::new(200, "elsewhere"));
+        let location = target.url("/elsewhere");
+        let origin = TestServer::start(move |_| {
+            TestResponse::new(302, "").with_header("Location", &location)
+        });
+
+        let mut req = Request::new(Method::Get, Url::parse(&origin.url("/")).unwrap());
+        req.insert_header("Authorization", "Bearer secret");
+        let mut resp = smol::block_on(fetch(req)).unwrap();
+
+        assert_eq!(smol::block_on(resp.body_string()).unwrap(), "elsewhere");
+        assert_eq!(
+            origin.requests()[0].header("authorization"),
+            Some("Bearer secret")
+        );
+        assert_eq!(target.requests()[0].header("authorization"), None);
+    }
+
+    #[test]
+    fn test_strips_gitlab_tokens_on_cross_origin_redirects() {
+        let target = TestServer::start(|_| TestResponse::new(200, "moved"));
+        let location = target.url("/api/v4/projects/new%2Frepo");
+        let origin = TestServer::start(move |_| {
+            TestResponse::new(301, "").with_header("Location", &location)
+        });
+
+        let mut req = Request::new(
+            Method::Get,
+            Url::parse(&origin.url("/api/v4/projects/old%2Frepo")).unwrap(),
+        );
+        req.insert_header("PRIVATE-TOKEN", "secret");
+        let mut resp = smol::block_on(fetch(req)).unwrap();
+
+        assert_eq!(smol::block_on(resp.body_string()).unwrap(), "moved");
+        assert_eq!(origin.requests()[0].header("private-token"), Some("secret"));
+        assert_eq!(target.requests()[0].header("private-token"), None);
+    }
+
+    #[test]
+    fn test_gives_up_on_redirect_loops() {
+        let server = TestServer::start(|_| TestResponse::new(302, "").with_header("Location", "/"));
+
+        let req = Request::new(Method::Get, Url::parse(&server.url("/")).unwrap());
+        let error = smol::block_on(fetch(req)).unwrap_err();
+
+        assert!(error.to_string().contains("Too many redirects"));
+        assert_eq!(server.requests().len(), MAX_REDIRECTS + 1);
+    }
+
+    #[test]
+    fn test_decompresses_gzipped_responses() {
+        let server = TestServer::start(|request| {
+            if request.header("accept-encoding") != Some("gzip") {
+                return TestResponse::new(200, "uncompressed");
+            }
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            std::io::Write::write_all(&mut encoder, b"compressed").unwrap();
+            TestResponse::new(200, encoder.finish().unwrap())
+                .with_header("Content-Encoding", "gzip")
+        });
+
+        let req = Request::new(Method::Get, Url::parse(&server.url("/")).unwrap());
+        let mut resp = smol::block_on(fetch(req)).unwrap();
+
+        assert!(resp.header("Content-Encoding").is_none());
+        assert_eq!(smol::block_on(resp.body_string()).unwrap(), "compressed");
+    }
+
+    fn fetch_from(server: &TestServer, path: &str, timeouts: &Timeouts) -> Result<Response> {
+        let req = Request::new(Method::Get, Url::parse(&server.url(path)).unwrap());
+        smol::block_on(fetch_with_timeouts(req, timeouts))
+    }
+
+    #[test]
+    fn test_reuses_connections() {
+        let server = TestServer::start(|_| TestResponse::new(200, "ok"));
+
+        fetch_from(&server, "/a", &Timeouts::default()).unwrap();
+        fetch_from(&server, "/b", &Timeouts::default()).unwrap();
+
+        let connections: Vec<usize> = server.requests().iter().map(|r| r.connection).collect();
+        assert_eq!(connections, [0, 0]);
+    }
+
+    #[test]
+    fn test_does_not_reuse_closed_connections() {
+        let server =
+            TestServer::start(|_| TestResponse::new(200, "ok").with_header("Connection", "close"));
+
+        fetch_from(&server, "/a", &Timeouts::default()).unwrap();
+        fetch_from(&server, "/b", &Timeouts::default()).unwrap();
+
+        let connections: Vec<usize> = server.requests().iter().map(|r| r.connection).collect();
+        assert_eq!(connections, [0, 1]);
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let server = TestServer::start(|_| {
+            std::thread::sleep(Duration::from_millis(500));
+            TestResponse::new(200, "late")
+        });
+        let timeouts = Timeouts {
+            read: Duration::from_millis(50),
+            ..Timeouts::default()
+        };
+
+        let error = fetch_from(&server, "/", &timeouts).unwrap_err();
+
+        assert_eq!(
+            Timeout::cause_of(&error),
+            Some(Timeout::Read(Duration::from_millis(50)))
+        );
+        assert!(is_transient_error(&error));
+    }
+
+    #[test]
+    fn test_total_timeout() {
+        let server = TestServer::start(|_| {
+            std::thread::sleep(Duration::from_millis(500));
+            TestResponse::new(200, "late")
+        });
+        let timeouts = Timeouts {
+            total: Duration::from_millis(50),
+            ..Timeouts::default()
+        };
+
+        let error = fetch_from(&server, "/", &timeouts).unwrap_err();
+
+        assert_eq!(
+            Timeout::cause_of(&error),
+            Some(Timeout::Total(Duration::from_millis(50)))
+        );
+        assert!(!is_transient_error(&error));
+    }
+
+    #[test]
+    fn test_retries_os_timeouts() {
+        let error = anyhow::Error::new(io::Error::from(io::ErrorKind::TimedOut))
+            .context("Failed to connect");
+
+        assert!(is_transient_error(&error));
+    }
+}
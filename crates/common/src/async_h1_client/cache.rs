@@ -0,0 +1,472 @@
+//! An on-disk cache of responses, so that repeated runs revalidate what they fetched before instead of downloading it again.
+//!
+//! Revalidation matters for GitHub in particular: `304 Not Modified` responses don't count against its rate limit.
+
+use std::{
+    env,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Error, Result};
+use http_types::{Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{fetch_with_retries, RetryPolicy, CREDENTIAL_HEADERS};
+
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+static CACHE: OnceLock<HttpCache> = OnceLock::new();
+
+/// Configures the cache used by [`super::fetch_cached`], unless it has been used already.
+pub fn init(cache: HttpCache) -> &'static HttpCache {
+    CACHE.get_or_init(|| cache)
+}
+
+/// The cache configured with [`init`], or the default one.
+pub fn get() -> &'static HttpCache {
+    CACHE.get_or_init(HttpCache::default)
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    /// `None` disables the cache.
+    dir: Option<PathBuf>,
+    /// Responses younger than this are used without asking the server.
+    ttl: Duration,
+    /// Never ask the server, but use cached responses of any age.
+    offline: bool,
+}
+
+/// The error of requests in offline mode whose responses aren't cached.
+#[derive(Debug)]
+pub struct NotCached {
+    pub url: String,
+}
+
+impl std::fmt::Display for NotCached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Not cached, and offline: {}", self.url)
+    }
+}
+
+impl std::error::Error for NotCached {}
+
+impl NotCached {
+    /// Whether `error` was caused by a response that isn't cached.
+    pub fn is_cause_of(error: &Error) -> bool {
+        error.chain().any(|cause| cause.is::<NotCached>())
+    }
+}
+
+/// Everything about a response but its body.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedHead {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Seconds since the epoch when the response was fetched or last revalidated
+    validated_at: u64,
+}
+
+impl CachedHead {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Default for HttpCache {
+    fn default() -> Self {
+        HttpCache::new(default_dir(), DEFAULT_TTL)
+    }
+}
+
+/// `$XDG_CACHE_HOME/deputui`, or `~/.cache/deputui`.
+pub fn default_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+
+    Some(cache_home.join("deputui"))
+}
+
+impl HttpCache {
+    pub fn new(dir: Option<PathBuf>, ttl: Duration) -> HttpCache {
+        HttpCache {
+            dir,
+            ttl,
+            offline: false,
+        }
+    }
+
+    pub fn with_offline(mut self, offline: bool) -> HttpCache {
+        self.offline = offline;
+        self
+    }
+
+    pub fn disabled() -> HttpCache {
+        HttpCache::new(None, DEFAULT_TTL)
+    }
+
+    /// Whether requests must not touch the network, see [`HttpCache::with_offline`].
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Answers `GET` requests from the cache while they're fresh, and revalidates them with `If-None-Match` and `If-Modified-Since` afterwards. Successful responses are stored, unless the server forbids it.
+    ///
+    /// Offline, requests are answered from the cache only, or fail with [`NotCached`].
+    pub async fn fetch(&self, mut req: Request, policy: &RetryPolicy) -> Result<Response> {
+        let not_cached = || {
+            Error::new(NotCached {
+                url: req.url().to_string(),
+            })
+        };
+
+        let Some(dir) = &self.dir else {
+            if self.offline {
+                return Err(not_cached());
+            }
+            return fetch_with_retries(req, policy).await;
+        };
+        if req.method() != Method::Get {
+            if self.offline {
+                return Err(not_cached());
+            }
+            return fetch_with_retries(req, policy).await;
+        }
+
+        let url = req.url().to_string();
+        let path = dir.join(cache_key(&req));
+        let cached = read_entry(&path, &url).await;
+
+        if self.offline {
+            return match cached {
+                Some((head, body)) => Ok(to_response(&head, body)),
+                None => Err(not_cached()),
+            };
+        }
+
+        if let Some((head, body)) = &cached {
+            if now().saturating_sub(head.validated_at) < self.ttl.as_secs() {
+                return Ok(to_response(head, body.clone()));
+            }
+            if let Some(etag) = head.header("etag") {
+                req.insert_header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = head.header("last-modified") {
+                req.insert_header("If-Modified-Since", last_modified);
+            }
+        }
+
+        let mut resp = fetch_with_retries(req, policy).await?;
+
+        match (resp.status(), cached) {
+            (StatusCode::NotModified, Some((mut head, body))) => {
+                head.validated_at = now();
+                // A cache that cannot be written is merely slow, so don't fail the request
+                write_entry(&path, &head, &body).await.ok();
+                Ok(to_response(&head, body))
+            }
+            (StatusCode::Ok, _) if !forbids_storing(&resp) => {
+                let body = resp.body_bytes().await.map_err(|e| e.into_inner())?;
+                let head = CachedHead {
+                    url,
+                    status: resp.status().into(),
+                    headers: stored_headers(&resp),
+                    validated_at: now(),
+                };
+                write_entry(&path, &head, &body).await.ok();
+                Ok(to_response(&head, body))
+            }
+            _ => Ok(resp),
+        }
+    }
+}
+
+/// The SHA-256 of the URL, the `Accept` header, as requests for the same URL may ask for different representations, and the [`CREDENTIAL_HEADERS`], as responses to them may be private. A digest is stable across builds, and keeps credentials out of file names.
+fn cache_key(req: &Request) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.url().as_str());
+    for name in ["Accept"].into_iter().chain(CREDENTIAL_HEADERS) {
+        hasher.update([0]);
+        if let Some(value) = req.header(name) {
+            hasher.update(value.as_str());
+        }
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn forbids_storing(resp: &Response) -> bool {
+    resp.header("Cache-Control")
+        .is_some_and(|cache_control| cache_control.as_str().contains("no-store"))
+}
+
+/// The headers worth restoring, i.e. not those describing the transfer of the original response.
+fn stored_headers(resp: &Response) -> Vec<(String, String)> {
+    resp.iter()
+        .filter(|(name, _)| {
+            ![
+                "connection",
+                "content-length",
+                "transfer-encoding",
+                "content-encoding",
+            ]
+            .contains(&name.as_str().to_lowercase().as_str())
+        })
+        .map(|(name, values)| (name.to_string(), values.as_str().to_string()))
+        .collect()
+}
+
+fn to_response(head: &CachedHead, body: Vec<u8>) -> Response {
+    let mut resp = Response::new(head.status);
+    for (name, value) in &head.headers {
+        resp.append_header(name.as_str(), value.as_str());
+    }
+    resp.set_body(body);
+    resp
+}
+
+/// Entries are a line of JSON for the head, followed by the body. Only entries of `url` are read, should keys ever collide.
+async fn read_entry(path: &Path, url: &str) -> Option<(CachedHead, Vec<u8>)> {
+    let bytes = smol::fs::read(path).await.ok()?;
+    let newline = bytes.iter().position(|&byte| byte == b'\n')?;
+    let head: CachedHead = serde_json::from_slice(&bytes[..newline]).ok()?;
+    if head.url != url {
+        return None;
+    }
+    Some((head, bytes[newline + 1..].to_vec()))
+}
+
+/// Writes to a temporary file first, so that concurrent readers never see half an entry. Entries may hold private responses, so only the user may read them.
+async fn write_entry(path: &Path, head: &CachedHead, body: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        smol::fs::create_dir_all(dir).await?;
+    }
+
+    let mut bytes = serde_json::to_vec(head)?;
+    bytes.push(b'\n');
+    bytes.extend_from_slice(body);
+
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let temporary = path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    smol::unblock({
+        let temporary = temporary.clone();
+        move || write_private(&temporary, &bytes)
+    })
+    .await?;
+    smol::fs::rename(&temporary, path).await?;
+    Ok(())
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(bytes)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestResponse, TestServer};
+    use url::Url;
+
+    fn fetch_body(cache: &HttpCache, server: &TestServer) -> String {
+        fetch_body_with(cache, server, "/foo", "")
+    }
+
+    fn fetch_body_with(cache: &HttpCache, server: &TestServer, path: &str, token: &str) -> String {
+        let mut req = Request::new(Method::Get, Url::parse(&server.url(path)).unwrap());
+        if !token.is_empty() {
+            req.insert_header("Authorization", format!("Bearer {}", token));
+        }
+        let mut resp = smol::block_on(cache.fetch(req, &RetryPolicy::default())).unwrap();
+        smol::block_on(resp.body_string()).unwrap()
+    }
+
+    fn etag_server() -> TestServer {
+        TestServer::start(|request| match request.header("if-none-match") {
+            Some("\"v1\"") => TestResponse::new(304, ""),
+            _ => TestResponse::new(200, "hello").with_header("ETag", "\"v1\""),
+        })
+    }
+
+    #[test]
+    fn test_fresh_responses_are_served_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(Some(dir.path().to_path_buf()), Duration::from_secs(60));
+        let server = etag_server();
+
+        assert_eq!(fetch_body(&cache, &server), "hello");
+        assert_eq!(fetch_body(&cache, &server), "hello");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_stale_responses_are_revalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(Some(dir.path().to_path_buf()), Duration::ZERO);
+        let server = etag_server();
+
+        assert_eq!(fetch_body(&cache, &server), "hello");
+        assert_eq!(fetch_body(&cache, &server), "hello");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].header("if-none-match"), None);
+        assert_eq!(requests[1].header("if-none-match"), Some("\"v1\""));
+    }
+
+    #[test]
+    fn test_revalidates_with_last_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(Some(dir.path().to_path_buf()), Duration::ZERO);
+        let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let server = TestServer::start(move |request| match request.header("if-modified-since") {
+            Some(since) if since == last_modified => TestResponse::new(304, ""),
+            _ => TestResponse::new(200, "hello").with_header("Last-Modified", last_modified),
+        });
+
+        assert_eq!(fetch_body(&cache, &server), "hello");
+        assert_eq!(fetch_body(&cache, &server), "hello");
+        assert_eq!(
+            server.requests()[1].header("if-modified-since"),
+            Some(last_modified)
+        );
+    }
+
+    #[test]
+    fn test_offline_serves_stale_responses_without_asking() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = etag_server();
+
+        let online = HttpCache::new(Some(dir.path().to_path_buf()), Duration::ZERO);
+        assert_eq!(fetch_body(&online, &server), "hello");
+
+        let offline = online.clone().with_offline(true);
+        assert_eq!(fetch_body(&offline, &server), "hello");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_offline_fails_for_uncached_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(Some(dir.path().to_path_buf()), DEFAULT_TTL).with_offline(true);
+        let server = etag_server();
+
+        let req = Request::new(Method::Get, Url::parse(&server.url("/foo")).unwrap());
+        let error = smol::block_on(cache.fetch(req, &RetryPolicy::default())).unwrap_err();
+
+        assert!(NotCached::is_cause_of(&error));
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_disabled_cache_always_fetches() {
+        let cache = HttpCache::disabled();
+        let server = etag_server();
+
+        fetch_body(&cache, &server);
+        fetch_body(&cache, &server);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].header("if-none-match"), None);
+    }
+
+    #[test]
+    fn test_errors_are_not_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(Some(dir.path().to_path_buf()), Duration::from_secs(60));
+        let server = TestServer::start(|_| TestResponse::new(404, "not found"));
+
+        fetch_body(&cache, &server);
+        fetch_body(&cache, &server);
+
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_cache_key_is_stable() {
+        let mut req = Request::new(
+            Method::Get,
+            Url::parse("https://registry.npmjs.org/foo").unwrap(),
+        );
+        req.insert_header("Accept", "application/json");
+
+        assert_eq!(
+            cache_key(&req),
+            "930a9084392f2eed39baccf087525997801e3de9d9d5e94903df142bf24170b4"
+        );
+    }
+
+    #[test]
+    fn test_authenticated_responses_are_cached_per_credential() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(Some(dir.path().to_path_buf()), Duration::from_secs(60));
+        let server = TestServer::start(|request| match request.header("authorization") {
+            Some(authorization) => TestResponse::new(200, authorization.to_string()),
+            None => TestResponse::new(200, "public"),
+        });
+
+        assert_eq!(fetch_body_with(&cache, &server, "/foo", "a"), "Bearer a");
+        assert_eq!(fetch_body_with(&cache, &server, "/foo", "a"), "Bearer a");
+        assert_eq!(fetch_body_with(&cache, &server, "/foo", "b"), "Bearer b");
+        assert_eq!(fetch_body_with(&cache, &server, "/foo", ""), "public");
+        assert_eq!(server.requests().len(), 3);
+
+        #[cfg(unix)]
+        for entry in std::fs::read_dir(dir.path()).unwrap() {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = entry.unwrap().metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_entries_of_other_urls_are_not_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = HttpCache::new(Some(dir.path().to_path_buf()), Duration::from_secs(60));
+        let server = TestServer::start(|request| TestResponse::new(200, request.target.clone()));
+
+        assert_eq!(fetch_body_with(&cache, &server, "/foo", ""), "/foo");
+        // As if the keys of both URLs collided
+        let key = |path: &str| {
+            cache_key(&Request::new(
+                Method::Get,
+                Url::parse(&server.url(path)).unwrap(),
+            ))
+        };
+        std::fs::copy(dir.path().join(key("/foo")), dir.path().join(key("/bar"))).unwrap();
+
+        assert_eq!(fetch_body_with(&cache, &server, "/bar", ""), "/bar");
+        assert_eq!(server.requests().len(), 2);
+    }
+}
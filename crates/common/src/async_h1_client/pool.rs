@@ -0,0 +1,150 @@
+//! Idle connections kept open for later requests to the same origin, which saves the DNS lookup, the TCP connect and the TLS handshake.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use smol::{
+    future::Future,
+    io::{AsyncRead, AsyncWrite},
+    Timer,
+};
+
+use super::timeout::Timeout;
+
+/// Servers commonly close idle connections after some seconds, so older ones are likely dead.
+const MAX_IDLE_TIME: Duration = Duration::from_secs(30);
+
+const MAX_IDLE_PER_ORIGIN: usize = 16;
+
+static POOL: Mutex<BTreeMap<String, Vec<IdleConnection>>> = Mutex::new(BTreeMap::new());
+
+pub trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<S: AsyncRead + AsyncWrite + Send + Unpin> Stream for S {}
+
+/// A connection that can be handed to `async_h1`, which takes ownership of its stream, and still be reused afterwards.
+///
+/// Reads and writes fail with [`Timeout::Read`] if the server makes no progress for too long.
+#[derive(Clone)]
+pub struct Connection(Arc<Mutex<TimedStream>>);
+
+struct TimedStream {
+    stream: Box<dyn Stream>,
+    read_timeout: Duration,
+    timer: Timer,
+    /// Whether a read or write is pending, i.e. the timer is running
+    waiting: bool,
+}
+
+struct IdleConnection {
+    connection: Connection,
+    since: Instant,
+}
+
+impl Connection {
+    pub fn new(stream: impl Stream + 'static, read_timeout: Duration) -> Connection {
+        Connection(Arc::new(Mutex::new(TimedStream {
+            stream: Box::new(stream),
+            read_timeout,
+            timer: Timer::never(),
+            waiting: false,
+        })))
+    }
+
+    pub fn set_read_timeout(&self, read_timeout: Duration) {
+        self.0.lock().unwrap().read_timeout = read_timeout;
+    }
+}
+
+/// An idle connection to `origin`, if there is one that's likely still alive.
+pub fn take(origin: &str) -> Option<Connection> {
+    let mut pool = POOL.lock().unwrap();
+    let idle = pool.get_mut(origin)?;
+    while let Some(IdleConnection { connection, since }) = idle.pop() {
+        if since.elapsed() < MAX_IDLE_TIME {
+            return Some(connection);
+        }
+    }
+    None
+}
+
+/// Keeps `connection` for the next request to `origin`. The response must have been read completely.
+pub fn put(origin: &str, connection: Connection) {
+    let mut pool = POOL.lock().unwrap();
+    let idle = pool.entry(origin.to_string()).or_default();
+    if idle.len() < MAX_IDLE_PER_ORIGIN {
+        idle.push(IdleConnection {
+            connection,
+            since: Instant::now(),
+        });
+    }
+}
+
+impl TimedStream {
+    /// Polls an I/O operation, failing if it hasn't made progress within the read timeout.
+    fn poll_timed<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: impl FnOnce(Pin<&mut dyn Stream>, &mut Context<'_>) -> Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        if !self.waiting {
+            self.timer.set_after(self.read_timeout);
+            self.waiting = true;
+        }
+
+        match poll(Pin::new(&mut *self.stream), cx) {
+            Poll::Ready(result) => {
+                self.waiting = false;
+                Poll::Ready(result)
+            }
+            Poll::Pending => match Pin::new(&mut self.timer).poll(cx) {
+                Poll::Ready(_) => {
+                    self.waiting = false;
+                    Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        Timeout::Read(self.read_timeout),
+                    )))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut stream = self.0.lock().unwrap();
+        stream.poll_timed(cx, |stream, cx| stream.poll_read(cx, buf))
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut stream = self.0.lock().unwrap();
+        stream.poll_timed(cx, |stream, cx| stream.poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut stream = self.0.lock().unwrap();
+        stream.poll_timed(cx, |stream, cx| stream.poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut stream = self.0.lock().unwrap();
+        Pin::new(&mut *stream.stream).poll_close(cx)
+    }
+}
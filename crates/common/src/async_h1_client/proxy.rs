@@ -0,0 +1,203 @@
+//! Tunnels through HTTP proxies configured like curl and npm do, with `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY`.
+
+use std::net::TcpStream;
+
+use anyhow::{bail, Result};
+use http_types::auth::BasicAuth;
+use percent_encoding::percent_decode_str;
+use smol::{prelude::*, Async};
+use url::Url;
+
+/// The limit for the response head of a proxy, so that a broken proxy cannot make us read forever.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// The proxy to reach `url` through, if any. Lowercase variables take precedence, as with curl.
+pub fn proxy_for(url: &Url, lookup_env: impl Fn(&str) -> Option<String>) -> Option<Url> {
+    let var = |name: &str| {
+        lookup_env(&name.to_lowercase())
+            .or_else(|| lookup_env(name))
+            .filter(|value| !value.is_empty())
+    };
+
+    let proxy = match url.scheme() {
+        "https" => var("HTTPS_PROXY"),
+        "http" => var("HTTP_PROXY"),
+        _ => None,
+    }?;
+
+    let host = url.host_str()?;
+    let port = url.port_or_known_default()?;
+    if var("NO_PROXY").is_some_and(|no_proxy| bypasses(&no_proxy, host, port)) {
+        return None;
+    }
+
+    // Proxies are often given without scheme, e.g. `proxy.corp.example:3128`
+    match proxy.contains("://") {
+        true => Url::parse(&proxy).ok(),
+        false => Url::parse(&format!("http://{}", proxy)).ok(),
+    }
+}
+
+/// Whether `NO_PROXY`, a list like `localhost,.corp.example,10.0.0.1:8080`, exempts the host. Domains match their subdomains as well.
+fn bypasses(no_proxy: &str, host: &str, port: u16) -> bool {
+    no_proxy
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+
+            let (entry_host, entry_port) = match entry.rsplit_once(':') {
+                Some((entry_host, entry_port)) => match entry_port.parse::<u16>() {
+                    Ok(entry_port) => (entry_host, Some(entry_port)),
+                    Err(_) => (entry, None),
+                },
+                None => (entry, None),
+            };
+            if entry_port.is_some_and(|entry_port| entry_port != port) {
+                return false;
+            }
+
+            let domain = entry_host.trim_start_matches('*').trim_start_matches('.');
+            host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_lowercase()
+                    .ends_with(&format!(".{}", domain.to_lowercase()))
+        })
+}
+
+/// Asks the proxy on the other end of `stream` to open a tunnel to `host:port`, through which the stream then leads.
+///
+/// Plain HTTP is tunneled as well, rather than sent to the proxy in absolute form.
+pub async fn tunnel(
+    stream: &mut Async<TcpStream>,
+    proxy: &Url,
+    host: &str,
+    port: u16,
+) -> Result<()> {
+    let mut head = format!(
+        "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
+        host = host,
+        port = port
+    );
+    if !proxy.username().is_empty() {
+        let username = percent_decode_str(proxy.username()).decode_utf8_lossy();
+        let password = percent_decode_str(proxy.password().unwrap_or_default()).decode_utf8_lossy();
+        let auth = BasicAuth::new(username, password);
+        head.push_str(&format!("Proxy-Authorization: {}\r\n", auth.value()));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+
+    // Read byte by byte, so that nothing of the tunneled connection is consumed
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        if stream.read(&mut byte).await? == 0 {
+            bail!("Proxy {} closed the connection", proxy);
+        }
+        response.push(byte[0]);
+        if response.len() > MAX_HEAD_SIZE {
+            bail!("Proxy {} sent too large a response", proxy);
+        }
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        bail!(
+            "Proxy {} refused to connect to {}:{}: {}",
+            proxy,
+            host,
+            port,
+            status_line
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy_for_with(url: &str, env: &[(&str, &str)]) -> Option<String> {
+        let url = Url::parse(url).unwrap();
+        proxy_for(&url, |name| {
+            env.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| value.to_string())
+        })
+        .map(|proxy| proxy.to_string())
+    }
+
+    #[test]
+    fn test_proxy_by_scheme() {
+        let env = [
+            ("HTTPS_PROXY", "http://secure.proxy:3128"),
+            ("HTTP_PROXY", "plain.proxy:8080"),
+        ];
+
+        assert_eq!(
+            proxy_for_with("https://registry.npmjs.org/foo", &env).as_deref(),
+            Some("http://secure.proxy:3128/")
+        );
+        assert_eq!(
+            proxy_for_with("http://registry.npmjs.org/foo", &env).as_deref(),
+            Some("http://plain.proxy:8080/")
+        );
+        assert_eq!(proxy_for_with("https://registry.npmjs.org/foo", &[]), None);
+    }
+
+    #[test]
+    fn test_lowercase_variables_take_precedence() {
+        let env = [
+            ("https_proxy", "http://lower:1"),
+            ("HTTPS_PROXY", "http://upper:2"),
+        ];
+
+        assert_eq!(
+            proxy_for_with("https://github.com", &env).as_deref(),
+            Some("http://lower:1/")
+        );
+    }
+
+    #[test]
+    fn test_no_proxy() {
+        let env =
+            |no_proxy: &'static str| [("HTTPS_PROXY", "http://proxy:3128"), ("NO_PROXY", no_proxy)];
+
+        for (url, no_proxy) in [
+            ("https://npm.corp.example", "npm.corp.example"),
+            ("https://npm.corp.example", ".corp.example"),
+            ("https://npm.corp.example", "*.corp.example"),
+            ("https://npm.corp.example", "localhost, corp.example"),
+            ("https://npm.corp.example", "npm.corp.example:443"),
+            ("https://NPM.corp.example", "npm.corp.example"),
+            ("https://github.com", "*"),
+        ] {
+            assert_eq!(
+                proxy_for_with(url, &env(no_proxy)),
+                None,
+                "{} {}",
+                url,
+                no_proxy
+            );
+        }
+
+        for (url, no_proxy) in [
+            ("https://github.com", "corp.example"),
+            ("https://notcorp.example", "corp.example"),
+            ("https://npm.corp.example", "npm.corp.example:8443"),
+        ] {
+            assert!(
+                proxy_for_with(url, &env(no_proxy)).is_some(),
+                "{} {}",
+                url,
+                no_proxy
+            );
+        }
+    }
+}
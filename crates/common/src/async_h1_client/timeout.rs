@@ -0,0 +1,80 @@
+//! How long to wait for servers before giving up on them, so that a stalled server cannot keep us waiting forever.
+
+use std::{sync::OnceLock, time::Duration};
+
+static TIMEOUTS: OnceLock<Timeouts> = OnceLock::new();
+
+/// Configures the timeouts of [`super::fetch`], unless it has been used already.
+pub fn init(timeouts: Timeouts) -> &'static Timeouts {
+    TIMEOUTS.get_or_init(|| timeouts)
+}
+
+/// The timeouts configured with [`init`], or the default ones.
+pub fn get() -> &'static Timeouts {
+    TIMEOUTS.get_or_init(Timeouts::default)
+}
+
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    /// For resolving the host, connecting, and the TLS handshake
+    pub connect: Duration,
+    /// For any progress while sending the request or receiving the response
+    pub read: Duration,
+    /// For a whole request, including redirects
+    pub total: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Duration::from_secs(10),
+            read: Duration::from_secs(30),
+            total: Duration::from_secs(120),
+        }
+    }
+}
+
+/// The error of requests that took too long, telling which of the [`Timeouts`] was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    Connect(Duration),
+    Read(Duration),
+    Total(Duration),
+}
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Timeout::Connect(duration) => {
+                write!(f, "Timed out connecting after {}s", duration.as_secs_f32())
+            }
+            Timeout::Read(duration) => write!(
+                f,
+                "Timed out waiting for the server after {}s",
+                duration.as_secs_f32()
+            ),
+            Timeout::Total(duration) => write!(
+                f,
+                "Timed out after {}s for the whole request",
+                duration.as_secs_f32()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+impl Timeout {
+    /// The timeout that caused `error`, if any. Read timeouts surface as I/O errors from deep within the HTTP library, so these are looked into as well.
+    pub fn cause_of(error: &anyhow::Error) -> Option<Timeout> {
+        error.chain().find_map(|cause| {
+            cause.downcast_ref::<Timeout>().copied().or_else(|| {
+                cause
+                    .downcast_ref::<std::io::Error>()?
+                    .get_ref()?
+                    .downcast_ref::<Timeout>()
+                    .copied()
+            })
+        })
+    }
+}
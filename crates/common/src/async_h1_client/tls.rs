@@ -0,0 +1,78 @@
+//! TLS connections that trust the CAs of `NODE_EXTRA_CA_CERTS` and `SSL_CERT_FILE` in addition to the system's, e.g. corporate ones.
+
+use std::{env, fs, sync::OnceLock};
+
+use anyhow::{anyhow, Result};
+use async_native_tls::{Certificate, TlsConnector};
+
+static EXTRA_CERTIFICATES: OnceLock<Result<Vec<Certificate>, String>> = OnceLock::new();
+
+pub fn connector() -> Result<TlsConnector> {
+    let certificates = EXTRA_CERTIFICATES
+        .get_or_init(load_extra_certificates)
+        .as_ref()
+        .map_err(|e| anyhow!("{}", e))?;
+
+    Ok(certificates
+        .iter()
+        .cloned()
+        .fold(TlsConnector::new(), TlsConnector::add_root_certificate))
+}
+
+/// Reads the PEM bundles that the variables point to. A bundle that cannot be read fails every connection, rather than the user wondering why their CA is ignored.
+fn load_extra_certificates() -> Result<Vec<Certificate>, String> {
+    let mut certificates = Vec::new();
+
+    for name in ["NODE_EXTRA_CA_CERTS", "SSL_CERT_FILE"] {
+        let Some(path) = env::var_os(name).filter(|path| !path.is_empty()) else {
+            continue;
+        };
+        let path = path.to_string_lossy();
+        let bundle = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Cannot read {} from {}: {}", path, name, e))?;
+
+        for block in pem_blocks(&bundle) {
+            let certificate = Certificate::from_pem(block.as_bytes())
+                .map_err(|e| format!("Invalid certificate in {} from {}: {}", path, name, e))?;
+            certificates.push(certificate);
+        }
+    }
+
+    Ok(certificates)
+}
+
+/// Splits a bundle into its certificates, as each must be parsed on its own.
+fn pem_blocks(bundle: &str) -> Vec<&str> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let mut blocks = Vec::new();
+    let mut rest = bundle;
+    while let Some(begin) = rest.find(BEGIN) {
+        let Some(end) = rest[begin..].find(END) else {
+            break;
+        };
+        let end = begin + end + END.len();
+        blocks.push(&rest[begin..end]);
+        rest = &rest[end..];
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pem_blocks() {
+        let bundle = "# Corporate root\n-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n\n-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----\n-----BEGIN CERTIFICATE-----\ntruncated";
+
+        assert_eq!(
+            pem_blocks(bundle),
+            [
+                "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----",
+                "-----BEGIN CERTIFICATE-----\nBBBB\n-----END CERTIFICATE-----",
+            ]
+        );
+    }
+}
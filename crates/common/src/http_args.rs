@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use clap::Args;
+
+use crate::async_h1_client::{
+    cache::{self, HttpCache},
+    timeout::{self, Timeouts},
+};
+
+/// Flags that control how deputui talks to the registry and to GitHub.
+#[derive(Args, Debug, Clone)]
+pub struct HttpArgs {
+    /// Neither read responses from the on-disk cache nor store them there
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// How long cached responses are used without asking the server whether they changed, e.g. `90s`, `10m`, `2h` or `1d`
+    #[arg(long, default_value = "5m", value_parser = parse_duration)]
+    pub cache_ttl: Duration,
+
+    /// Don't touch the network, but use cached responses regardless of their age, e.g. on a plane
+    #[arg(long, conflicts_with = "no_cache")]
+    pub offline: bool,
+
+    /// How long to wait for a connection to a server, including the TLS handshake
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    pub connect_timeout: Duration,
+
+    /// How long to wait for a server that stopped sending or receiving
+    #[arg(long, default_value = "30s", value_parser = parse_duration)]
+    pub read_timeout: Duration,
+
+    /// How long to wait for a whole response, including redirects
+    #[arg(long, default_value = "2m", value_parser = parse_duration)]
+    pub timeout: Duration,
+}
+
+impl HttpArgs {
+    /// Configures the HTTP client of this process. Must be called before the first request.
+    pub fn init(&self) {
+        let dir = match self.no_cache {
+            true => None,
+            false => cache::default_dir(),
+        };
+        cache::init(HttpCache::new(dir, self.cache_ttl).with_offline(self.offline));
+        timeout::init(Timeouts {
+            connect: self.connect_timeout,
+            read: self.read_timeout,
+            total: self.timeout,
+        });
+    }
+}
+
+/// Parses a number of seconds, optionally with a unit of `s`, `m`, `h` or `d`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, unit_seconds) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 60 * 60),
+        Some((i, 'd')) => (&value[..i], 24 * 60 * 60),
+        _ => (value, 1),
+    };
+
+    number
+        .parse::<u64>()
+        .map(|number| Duration::from_secs(number * unit_seconds))
+        .map_err(|_| format!("invalid duration: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("ten minutes").is_err());
+    }
+}
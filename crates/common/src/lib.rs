@@ -1,4 +1,11 @@
 pub mod async_h1_client;
+pub mod http_args;
 pub mod npm_registry;
+pub mod npmrc;
+pub mod outdated;
+pub mod project;
 pub mod release;
+pub mod repository;
 pub mod semver;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_server;
@@ -1,56 +1,613 @@
-use crate::{async_h1_client, release::Release, semver::Semver};
-use anyhow::Result;
+use crate::{
+    async_h1_client,
+    npmrc::{self, Npmrc},
+    outdated::OutdatedPackage,
+    release::{DependencyType, Release, UpdateKind},
+    repository::Repository,
+    semver::Semver,
+};
+use anyhow::{anyhow, bail, Result};
+use http_types::{Method, Request};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use serde::Deserialize;
+use smol::io::AsyncReadExt;
 use std::collections::BTreeMap;
-
-#[derive(Debug, Deserialize)]
-pub struct Repository {
-    pub url: String,
-    pub r#type: String,
-}
+use url::Url;
 
 #[derive(Debug, Deserialize)]
 pub struct NpmPackage {
     pub name: String,
-    pub repository: Repository,
+    #[serde(default)]
+    pub repository: Option<Repository>,
     pub versions: BTreeMap<String, NpmVersion>,
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: BTreeMap<String, String>,
+    /// Which document the registry sent, which may be the full one even if the abbreviated one was asked for
+    #[serde(skip)]
+    pub metadata: Metadata,
+}
+
+/// Decides which packages, and which of their versions between the current and the latest version, are worth reviewing.
+#[derive(Debug, Clone)]
+pub struct ReleaseFilter {
+    /// Only review dependencies of these types, or all if empty. Packages of unknown type are always reviewed.
+    pub dependency_types: Vec<DependencyType>,
+    pub kinds: Vec<UpdateKind>,
+    pub include_prereleases: bool,
+    /// Also list the patches of each minor line instead of only its first release, e.g. `1.3.1` and `1.3.2` next to `1.3.0`.
+    pub include_intermediate_patches: bool,
+}
+
+impl Default for ReleaseFilter {
+    fn default() -> Self {
+        ReleaseFilter {
+            dependency_types: Vec::new(),
+            kinds: vec![UpdateKind::Minor],
+            include_prereleases: false,
+            include_intermediate_patches: false,
+        }
+    }
 }
 
+/// The manifest of a single version. Its repository is more accurate than the package's, which is that of the latest version: packages get renamed, transferred, or moved into monorepos.
 #[derive(Debug, Deserialize)]
 pub struct NpmVersion {
     pub name: String,
     pub version: String,
+    #[serde(default)]
+    pub repository: Option<Repository>,
+    #[serde(rename = "gitHead", default)]
+    pub git_head: Option<String>,
+    #[serde(default)]
+    pub dist: Option<NpmDist>,
+}
+
+/// Where the version is published.
+#[derive(Debug, Deserialize)]
+pub struct NpmDist {
+    /// The URL of the package's tarball, e.g. `https://registry.npmjs.org/foo/-/foo-1.0.0.tgz`
+    #[serde(default)]
+    pub tarball: Option<String>,
+}
+
+/// Which document of a package to fetch from the registry.
+///
+/// See https://github.com/npm/registry/blob/main/docs/responses/package-metadata.md
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Metadata {
+    /// The abbreviated document that package managers install from. It has the versions and dist-tags, but no repository, and is a fraction of the size.
+    Abbreviated,
+    /// The full document, with the repository and `gitHead` of every version.
+    #[default]
+    Full,
+}
+
+impl Metadata {
+    fn accept_header(self) -> &'static str {
+        match self {
+            Metadata::Abbreviated => {
+                "application/vnd.npm.install-v1+json; q=1.0, application/json; q=0.8, */*"
+            }
+            Metadata::Full => "application/json",
+        }
+    }
+
+    /// The document that a response with the given `Content-Type` is.
+    fn of_content_type(content_type: Option<&str>) -> Metadata {
+        match content_type {
+            Some(content_type)
+                if content_type.starts_with("application/vnd.npm.install-v1+json") =>
+            {
+                Metadata::Abbreviated
+            }
+            _ => Metadata::Full,
+        }
+    }
+}
+
+/// Characters that JavaScript's `encodeURIComponent` escapes, which is what npm uses for package names.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'!')
+    .remove(b'~')
+    .remove(b'*')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')');
+
+/// The URL of a package's document on `registry`, which must end with a slash.
+///
+/// The slash of scoped packages is escaped, e.g. `@scope%2fname`, as documented for the registry API.
+pub fn package_url(registry: &str, package_name: &str) -> Result<Url> {
+    let escaped_name = match package_name.strip_prefix('@') {
+        Some(scoped) => {
+            format!("@{}", utf8_percent_encode(scoped, COMPONENT)).replace("%2F", "%2f")
+        }
+        None => utf8_percent_encode(package_name, COMPONENT).to_string(),
+    };
+
+    Url::parse(&format!("{}{}", registry, escaped_name))
+        .map_err(|e| anyhow!("Invalid registry URL {}: {}", registry, e))
+}
+
+impl ReleaseFilter {
+    pub fn includes_dependency_type(&self, dependency_type: Option<DependencyType>) -> bool {
+        match dependency_type {
+            Some(dependency_type) => {
+                self.dependency_types.is_empty() || self.dependency_types.contains(&dependency_type)
+            }
+            None => true,
+        }
+    }
+
+    /// Fails if dependency types are filtered but none of the `packages` has one, e.g. from `npm outdated --json` without `--long`, as the filter would let all of them through.
+    pub fn check_dependency_types(&self, packages: &[OutdatedPackage]) -> Result<()> {
+        if !self.dependency_types.is_empty()
+            && !packages.is_empty()
+            && packages
+                .iter()
+                .all(|package| package.dependency_type.is_none())
+        {
+            bail!("--only needs the dependency types of the packages, which the input doesn't have. With npm, run `npm outdated --json --long`");
+        }
+        Ok(())
+    }
 }
 
 impl NpmPackage {
-    pub async fn fetch(package_name: &str) -> Result<NpmPackage> {
-        let url = format!("https://registry.npmjs.org/{}", package_name);
-        let response = async_h1_client::get(&url).await?;
+    /// Fetches the package from the registry configured for it in `.npmrc`, see [`npmrc`].
+    pub async fn fetch(package_name: &str, metadata: Metadata) -> Result<NpmPackage> {
+        Self::fetch_with(npmrc::get(), package_name, metadata).await
+    }
+
+    pub async fn fetch_with(
+        npmrc: &Npmrc,
+        package_name: &str,
+        metadata: Metadata,
+    ) -> Result<NpmPackage> {
+        let url = package_url(npmrc.registry_for(package_name), package_name)?;
+
+        let mut req = Request::new(Method::Get, url.clone());
+        req.insert_header("Accept", metadata.accept_header());
+        if let Some(token) = npmrc.auth_token_for(url.as_str()) {
+            req.insert_header("Authorization", format!("Bearer {}", token));
+        }
 
-        serde_json::from_str(&response)
-            .map_err(|e| anyhow::anyhow!("Failed to parse NPM registry response: {}", e))
+        let mut resp = async_h1_client::fetch_cached(req).await?;
+
+        if resp.status() != 200 {
+            bail!("NPM registry error for {}: {}", package_name, resp.status());
+        }
+
+        let metadata =
+            Metadata::of_content_type(resp.header("Content-Type").map(|values| values.as_str()));
+        let mut body = Vec::new();
+        resp.read_to_end(&mut body).await?;
+        let response_text = String::from_utf8_lossy(&body);
+
+        let package: NpmPackage = serde_json::from_str(&response_text)
+            .map_err(|e| anyhow!("Failed to parse NPM registry response: {}", e))?;
+        Ok(NpmPackage {
+            metadata,
+            ..package
+        })
     }
 
-    fn iter_versions(&self) -> impl Iterator<Item = &String> {
-        self.versions.keys()
+    /// Whether some of `releases`, which are from this document, lack what only the full document has: the repository, `gitHead` or dist info of their version.
+    pub fn lacks_full_metadata(&self, releases: &[Release]) -> bool {
+        self.metadata == Metadata::Abbreviated
+            && releases.iter().any(|release| {
+                release.repository_url.is_none()
+                    || release.git_head.is_none()
+                    || release.tarball_url.is_none()
+            })
     }
 
-    pub async fn fetch_releases(&self, current: Semver, latest: Semver) -> Result<Vec<Release>> {
+    pub async fn fetch_releases(
+        &self,
+        current: Semver,
+        latest: Semver,
+        filter: &ReleaseFilter,
+    ) -> Result<Vec<Release>> {
         let all_versions = self
-            .iter_versions()
-            .filter_map(|version| version.parse::<Semver>().ok());
+            .versions
+            .iter()
+            .filter_map(|(version, manifest)| Some((version.parse::<Semver>().ok()?, manifest)))
+            .filter(|(semver, _)| filter.include_prereleases || !semver.is_prerelease());
+
+        let updates = all_versions
+            .filter(|(semver, _)| semver.is_at_most(&latest))
+            .filter_map(|(semver, manifest)| {
+                let kind = semver.update_kind_of(&current)?;
+                // Steps along the pre-release track the project is on are wanted whatever their kind
+                let is_wanted = (filter.kinds.contains(&kind)
+                    && (semver.starts(kind)
+                        || (filter.include_intermediate_patches
+                            && semver.is_patch_of_start(kind))))
+                    || (filter.include_prereleases && semver.is_prerelease_update_of(&current));
+                is_wanted.then_some((semver, kind, manifest))
+            });
 
-        let minor_updates = all_versions
-            .filter(|semver| semver.is_minor_update_of(&current) && semver.is_at_most(&latest));
+        let releases: Vec<Release> = updates
+            .map(|(semver, kind, manifest)| {
+                let repository = manifest.repository.as_ref().or(self.repository.as_ref());
 
-        let releases: Vec<Release> = minor_updates
-            .map(|semver| Release {
-                package: self.name.clone(),
-                semver: semver.to_string(),
-                repository_url: self.repository.url.clone(),
+                Release {
+                    package: self.name.clone(),
+                    semver: semver.to_string(),
+                    repository_url: repository.and_then(Repository::web_url),
+                    kind,
+                    dependents: Vec::new(),
+                    dependency_type: None,
+                    directory: repository
+                        .and_then(Repository::directory)
+                        .map(str::to_string),
+                    git_head: manifest.git_head.clone(),
+                    tarball_url: manifest.dist.as_ref().and_then(|dist| dist.tarball.clone()),
+                }
             })
             .collect();
 
         Ok(releases)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestResponse, TestServer};
+
+    fn package_with_versions(versions: &[&str]) -> NpmPackage {
+        NpmPackage {
+            name: "foo".to_string(),
+            repository: Some(Repository::Shorthand("foo/foo".to_string())),
+            versions: versions
+                .iter()
+                .map(|version| {
+                    (
+                        version.to_string(),
+                        NpmVersion {
+                            name: "foo".to_string(),
+                            version: version.to_string(),
+                            repository: None,
+                            git_head: None,
+                            dist: None,
+                        },
+                    )
+                })
+                .collect(),
+            dist_tags: BTreeMap::new(),
+            metadata: Metadata::Full,
+        }
+    }
+
+    fn fetch_semvers(
+        package: &NpmPackage,
+        current: &str,
+        latest: &str,
+        filter: &ReleaseFilter,
+    ) -> Vec<String> {
+        let releases = smol::block_on(package.fetch_releases(
+            current.parse().unwrap(),
+            latest.parse().unwrap(),
+            filter,
+        ))
+        .unwrap();
+
+        releases.into_iter().map(|release| release.semver).collect()
+    }
+
+    #[test]
+    fn test_fetch_releases_skips_prereleases_by_default() {
+        let package = package_with_versions(&["1.0.0", "1.1.0-beta.1", "1.1.0", "1.2.0-rc.1"]);
+
+        let semvers = fetch_semvers(&package, "1.0.0", "1.1.0", &ReleaseFilter::default());
+
+        assert_eq!(semvers, vec!["1.1.0"]);
+    }
+
+    #[test]
+    fn test_fetch_releases_includes_prereleases_if_requested() {
+        let package = package_with_versions(&[
+            "1.0.0",
+            "1.1.0-beta.1",
+            "1.1.0",
+            "2.0.0-beta.1",
+            "2.0.0-beta.2",
+        ]);
+        let filter = ReleaseFilter {
+            include_prereleases: true,
+            ..Default::default()
+        };
+
+        let semvers = fetch_semvers(&package, "1.0.0", "1.1.0", &filter);
+        assert_eq!(semvers, vec!["1.1.0", "1.1.0-beta.1"]);
+
+        let semvers = fetch_semvers(&package, "2.0.0-beta.1", "2.0.0-beta.2", &filter);
+        assert_eq!(semvers, vec!["2.0.0-beta.2"]);
+    }
+
+    #[test]
+    fn test_dependency_types_are_required_to_filter_by_them() {
+        let package = |dependency_type| OutdatedPackage {
+            name: "foo".to_string(),
+            current: "1.0.0".to_string(),
+            latest: "1.1.0".to_string(),
+            dependents: Vec::new(),
+            dependency_type,
+        };
+        let only_prod = ReleaseFilter {
+            dependency_types: vec![DependencyType::Prod],
+            ..ReleaseFilter::default()
+        };
+
+        assert!(only_prod.check_dependency_types(&[package(None)]).is_err());
+        assert!(only_prod
+            .check_dependency_types(&[package(None), package(Some(DependencyType::Dev))])
+            .is_ok());
+        assert!(only_prod.check_dependency_types(&[]).is_ok());
+        assert!(ReleaseFilter::default()
+            .check_dependency_types(&[package(None)])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_fetch_releases_filters_by_update_kind() {
+        let package = package_with_versions(&[
+            "1.0.0", "1.0.1", "1.1.0", "1.1.1", "1.2.0", "2.0.0", "2.0.1", "2.1.0", "3.0.0",
+        ]);
+
+        let cases = [
+            (vec![UpdateKind::Patch], vec!["1.0.1"]),
+            (vec![UpdateKind::Minor], vec!["1.1.0", "1.2.0"]),
+            (vec![UpdateKind::Major], vec!["2.0.0"]),
+            (
+                vec![UpdateKind::Patch, UpdateKind::Minor, UpdateKind::Major],
+                vec!["1.0.1", "1.1.0", "1.2.0", "2.0.0"],
+            ),
+        ];
+
+        for (kinds, expected) in cases {
+            let filter = ReleaseFilter {
+                kinds,
+                ..Default::default()
+            };
+            let semvers = fetch_semvers(&package, "1.0.0", "2.1.0", &filter);
+            assert_eq!(semvers, expected, "{:?}", filter.kinds);
+        }
+    }
+
+    #[test]
+    fn test_fetch_releases_includes_intermediate_patches_if_requested() {
+        let package = package_with_versions(&[
+            "1.2.3", "1.2.4", "1.3.0", "1.3.1", "1.3.2", "1.4.0", "1.4.5", "1.4.6",
+        ]);
+        let filter = ReleaseFilter {
+            include_intermediate_patches: true,
+            ..Default::default()
+        };
+
+        let semvers = fetch_semvers(&package, "1.2.3", "1.4.5", &filter);
+
+        assert_eq!(semvers, vec!["1.3.0", "1.3.1", "1.3.2", "1.4.0", "1.4.5"]);
+    }
+
+    #[test]
+    fn test_intermediate_patches_of_major_updates_exclude_minors() {
+        let package = package_with_versions(&["1.0.0", "2.0.0", "2.0.1", "2.1.0", "2.1.1"]);
+        let filter = ReleaseFilter {
+            kinds: vec![UpdateKind::Major],
+            include_intermediate_patches: true,
+            ..Default::default()
+        };
+
+        let semvers = fetch_semvers(&package, "1.0.0", "2.1.1", &filter);
+
+        assert_eq!(semvers, vec!["2.0.0", "2.0.1"]);
+    }
+
+    #[test]
+    fn test_packages_without_repository_have_releases() {
+        let package: NpmPackage = serde_json::from_str(
+            r#"{ "name": "foo", "versions": { "1.0.0": { "name": "foo", "version": "1.0.0" }, "1.1.0": { "name": "foo", "version": "1.1.0" } } }"#,
+        )
+        .unwrap();
+
+        let releases = smol::block_on(package.fetch_releases(
+            "1.0.0".parse().unwrap(),
+            "1.1.0".parse().unwrap(),
+            &ReleaseFilter::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].repository_url, None);
+    }
+
+    #[test]
+    fn test_releases_use_the_repository_of_their_version() {
+        let package: NpmPackage = serde_json::from_str(
+            r#"{
+                "name": "foo",
+                "repository": "new-org/monorepo",
+                "versions": {
+                    "1.0.0": { "name": "foo", "version": "1.0.0" },
+                    "1.1.0": { "name": "foo", "version": "1.1.0", "repository": "old-org/foo", "gitHead": "abc123", "dist": { "tarball": "https://registry.npmjs.org/foo/-/foo-1.1.0.tgz" } },
+                    "1.2.0": {
+                        "name": "foo",
+                        "version": "1.2.0",
+                        "repository": { "type": "git", "url": "git+https://github.com/new-org/monorepo.git", "directory": "packages/foo" }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let releases = smol::block_on(package.fetch_releases(
+            "1.0.0".parse().unwrap(),
+            "1.2.0".parse().unwrap(),
+            &ReleaseFilter::default(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            releases[0].repository_url.as_deref(),
+            Some("https://github.com/old-org/foo")
+        );
+        assert_eq!(releases[0].git_head.as_deref(), Some("abc123"));
+        assert_eq!(
+            releases[0].tarball_url.as_deref(),
+            Some("https://registry.npmjs.org/foo/-/foo-1.1.0.tgz")
+        );
+        assert_eq!(releases[0].directory, None);
+        assert_eq!(
+            releases[1].repository_url.as_deref(),
+            Some("https://github.com/new-org/monorepo")
+        );
+        assert_eq!(releases[1].directory.as_deref(), Some("packages/foo"));
+    }
+
+    #[test]
+    fn test_package_url() {
+        let cases = [
+            ("lodash", "https://registry.npmjs.org/lodash"),
+            ("@types/node", "https://registry.npmjs.org/@types%2fnode"),
+            (
+                "@scope/a.b_c-d~",
+                "https://registry.npmjs.org/@scope%2fa.b_c-d~",
+            ),
+            ("odd+name?#", "https://registry.npmjs.org/odd%2Bname%3F%23"),
+        ];
+
+        for (package_name, expected) in cases {
+            let url = package_url("https://registry.npmjs.org/", package_name).unwrap();
+            assert_eq!(url.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_fetch_requests_escaped_names() {
+        let server = TestServer::start(|request| {
+            let name = match request.target.as_str() {
+                "/npm/lodash" => "lodash",
+                "/npm/@types%2fnode" => "@types/node",
+                "/npm/odd%2Bname%3F%23" => "odd+name?#",
+                _ => return TestResponse::new(404, "{}"),
+            };
+            let body = format!(
+                r#"{{ "name": "{}", "repository": {{ "type": "git", "url": "https://github.com/foo/foo.git" }}, "versions": {{}} }}"#,
+                name
+            );
+            TestResponse::new(200, body)
+        });
+
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(&format!("registry={}", server.url("/npm")), |_| None);
+
+        for package_name in ["lodash", "@types/node", "odd+name?#"] {
+            let package =
+                smol::block_on(NpmPackage::fetch_with(&npmrc, package_name, Metadata::Full))
+                    .unwrap();
+            assert_eq!(package.name, package_name);
+        }
+
+        let targets: Vec<String> = server.requests().into_iter().map(|r| r.target).collect();
+        assert_eq!(
+            targets,
+            vec!["/npm/lodash", "/npm/@types%2fnode", "/npm/odd%2Bname%3F%23"]
+        );
+    }
+
+    #[test]
+    fn test_fetch_fails_for_unknown_packages() {
+        let server = TestServer::start(|_| TestResponse::new(404, "{}"));
+
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(&format!("registry={}", server.url("/")), |_| None);
+
+        let result = smol::block_on(NpmPackage::fetch_with(
+            &npmrc,
+            "@scope/missing",
+            Metadata::Full,
+        ));
+        assert!(result.is_err());
+    }
+
+    /// A package with many versions, as both the full and the abbreviated document would list it.
+    fn packument(metadata: Metadata) -> String {
+        let versions: Vec<String> = (0..100)
+            .map(|minor| {
+                let version = format!("1.{}.0", minor);
+                let dist = format!(
+                    r#""dist": {{ "tarball": "https://registry.example/foo/-/foo-{}.tgz" }}"#,
+                    version
+                );
+                let manifest = match metadata {
+                    Metadata::Abbreviated => {
+                        format!(r#"{{ "name": "foo", "version": "{}", {} }}"#, version, dist)
+                    }
+                    Metadata::Full => format!(
+                        r#"{{ "name": "foo", "version": "{}", "description": "{}", "readme": "{}", "repository": "foo/foo", "gitHead": "abc123", {} }}"#,
+                        version,
+                        "A package that does foo. ".repeat(4),
+                        "Lorem ipsum dolor sit amet. ".repeat(40),
+                        dist
+                    ),
+                };
+                format!(r#""{}": {}"#, version, manifest)
+            })
+            .collect();
+
+        let repository = match metadata {
+            Metadata::Abbreviated => "",
+            Metadata::Full => r#""repository": "foo/foo", "#,
+        };
+
+        format!(
+            r#"{{ "name": "foo", {}"dist-tags": {{ "latest": "1.99.0" }}, "versions": {{ {} }} }}"#,
+            repository,
+            versions.join(", ")
+        )
+    }
+
+    #[test]
+    fn test_abbreviated_metadata_is_smaller() {
+        let server = TestServer::start(|request| {
+            let metadata = match request.header("accept") {
+                Some(accept) if accept.starts_with("application/vnd.npm.install-v1+json") => {
+                    Metadata::Abbreviated
+                }
+                _ => Metadata::Full,
+            };
+            let content_type = match metadata {
+                Metadata::Abbreviated => "application/vnd.npm.install-v1+json",
+                Metadata::Full => "application/json",
+            };
+            TestResponse::new(200, packument(metadata)).with_header("Content-Type", content_type)
+        });
+
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(&format!("registry={}", server.url("/")), |_| None);
+
+        let abbreviated =
+            smol::block_on(NpmPackage::fetch_with(&npmrc, "foo", Metadata::Abbreviated)).unwrap();
+        let abbreviated_bytes = server.response_bytes();
+        let full = smol::block_on(NpmPackage::fetch_with(&npmrc, "foo", Metadata::Full)).unwrap();
+        let full_bytes = server.response_bytes() - abbreviated_bytes;
+
+        assert_eq!(abbreviated.versions.len(), full.versions.len());
+        assert_eq!(abbreviated.dist_tags["latest"], "1.99.0");
+        assert_eq!(abbreviated.metadata, Metadata::Abbreviated);
+        assert_eq!(full.metadata, Metadata::Full);
+        assert!(abbreviated.repository.is_none());
+        assert!(full.repository.is_some());
+        assert!(
+            abbreviated_bytes * 10 < full_bytes,
+            "abbreviated: {} bytes, full: {} bytes",
+            abbreviated_bytes,
+            full_bytes
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Registry configuration from `.npmrc` files and the environment, read like npm reads it.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const DEFAULT_REGISTRY: &str = "https://registry.npmjs.org/";
+
+static NPMRC: OnceLock<Npmrc> = OnceLock::new();
+
+#[derive(Debug, Clone, Default)]
+pub struct Npmrc {
+    registry: Option<String>,
+    /// Registries of scopes, e.g. `@corp` → `https://npm.corp.example/`
+    scoped_registries: BTreeMap<String, String>,
+    /// Tokens by registry URL without scheme, e.g. `//npm.corp.example/` → `secret`
+    auth_tokens: BTreeMap<String, String>,
+}
+
+/// Loads the configuration of the project in `project_dir`, unless some configuration has been loaded already.
+pub fn init(project_dir: &Path) -> &'static Npmrc {
+    NPMRC.get_or_init(|| Npmrc::load(project_dir))
+}
+
+/// The configuration of the project in the current working directory, unless [`init`] was called with another one.
+pub fn get() -> &'static Npmrc {
+    NPMRC.get_or_init(|| Npmrc::load(Path::new(".")))
+}
+
+impl Npmrc {
+    /// Reads the user's `~/.npmrc`, then the project's `.npmrc`, then `NPM_CONFIG_*` environment variables. Later ones take precedence.
+    pub fn load(project_dir: &Path) -> Npmrc {
+        let mut npmrc = Npmrc::default();
+
+        let user_config = env::var("NPM_CONFIG_USERCONFIG")
+            .map(PathBuf::from)
+            .ok()
+            .or_else(|| {
+                env::var("HOME")
+                    .ok()
+                    .map(|home| Path::new(&home).join(".npmrc"))
+            });
+        let project_config = Some(project_dir.join(".npmrc"));
+
+        for file in [user_config, project_config].into_iter().flatten() {
+            if let Ok(content) = fs::read_to_string(file) {
+                npmrc.parse(&content, |name| env::var(name).ok());
+            }
+        }
+
+        let registry = env::var("NPM_CONFIG_REGISTRY").or_else(|_| env::var("npm_config_registry"));
+        if let Ok(registry) = registry {
+            npmrc.registry = Some(with_trailing_slash(registry));
+        }
+
+        npmrc
+    }
+
+    /// Applies the `key=value` lines of an `.npmrc` file, expanding `${VAR}` references with `lookup_env`.
+    pub fn parse(&mut self, content: &str, lookup_env: impl Fn(&str) -> Option<String>) {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            let value = expand_env(value.trim().trim_matches('"'), &lookup_env);
+
+            if key == "registry" {
+                self.registry = Some(with_trailing_slash(value));
+            } else if let Some(scope) = key.strip_suffix(":registry") {
+                self.scoped_registries
+                    .insert(scope.to_string(), with_trailing_slash(value));
+            } else if let Some(registry) = key.strip_suffix(":_authToken") {
+                self.auth_tokens.insert(registry.to_string(), value);
+            }
+        }
+    }
+
+    /// The registry to fetch a package from, with a trailing slash. Scoped packages may come from a registry of their own.
+    pub fn registry_for(&self, package_name: &str) -> &str {
+        package_name
+            .split_once('/')
+            .filter(|(scope, _)| scope.starts_with('@'))
+            .and_then(|(scope, _)| self.scoped_registries.get(scope))
+            .or(self.registry.as_ref())
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_REGISTRY)
+    }
+
+    /// The token configured for the most specific registry path that `url` is part of. Paths match whole segments, so `//npm.corp.example` doesn't match `//npm.corp.example.evil.net/`.
+    pub fn auth_token_for(&self, url: &str) -> Option<&str> {
+        let (_, without_scheme) = url.split_once(':')?;
+
+        self.auth_tokens
+            .iter()
+            .filter(|(registry, _)| {
+                without_scheme
+                    .strip_prefix(registry.as_str())
+                    .is_some_and(|rest| {
+                        registry.ends_with('/') || rest.is_empty() || rest.starts_with('/')
+                    })
+            })
+            .max_by_key(|(registry, _)| registry.len())
+            .map(|(_, token)| token.as_str())
+    }
+}
+
+fn with_trailing_slash(url: String) -> String {
+    match url.ends_with('/') {
+        true => url,
+        false => format!("{}/", url),
+    }
+}
+
+fn expand_env(value: &str, lookup_env: &impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&lookup_env(&rest[start + 2..start + end]).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Npmrc {
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(content, |name| match name {
+            "CORP_TOKEN" => Some("corp-secret".to_string()),
+            _ => None,
+        });
+        npmrc
+    }
+
+    #[test]
+    fn test_default_registry() {
+        let npmrc = parse("");
+        assert_eq!(npmrc.registry_for("foo"), "https://registry.npmjs.org/");
+        assert_eq!(
+            npmrc.registry_for("@corp/foo"),
+            "https://registry.npmjs.org/"
+        );
+    }
+
+    #[test]
+    fn test_scoped_registries() {
+        let npmrc = parse(
+            "registry=https://mirror.example/npm\n\
+             @corp:registry=https://npm.corp.example/\n",
+        );
+
+        assert_eq!(npmrc.registry_for("foo"), "https://mirror.example/npm/");
+        assert_eq!(
+            npmrc.registry_for("@other/foo"),
+            "https://mirror.example/npm/"
+        );
+        assert_eq!(npmrc.registry_for("@corp/foo"), "https://npm.corp.example/");
+    }
+
+    #[test]
+    fn test_auth_tokens() {
+        let npmrc = parse(
+            "# Comment\n\
+             //npm.corp.example/:_authToken=${CORP_TOKEN}\n\
+             //npm.corp.example/private/:_authToken=\"private-secret\"\n",
+        );
+
+        assert_eq!(
+            npmrc.auth_token_for("https://npm.corp.example/@corp%2ffoo"),
+            Some("corp-secret")
+        );
+        assert_eq!(
+            npmrc.auth_token_for("https://npm.corp.example/private/foo"),
+            Some("private-secret")
+        );
+        assert_eq!(npmrc.auth_token_for("https://registry.npmjs.org/foo"), None);
+    }
+
+    #[test]
+    fn test_auth_tokens_match_whole_segments() {
+        let npmrc = parse(
+            "//npm.corp.example:_authToken=corp-secret
+             //npm.corp.example/private:_authToken=private-secret
+",
+        );
+
+        assert_eq!(
+            npmrc.auth_token_for("https://npm.corp.example/foo"),
+            Some("corp-secret")
+        );
+        assert_eq!(
+            npmrc.auth_token_for("https://npm.corp.example/private/foo"),
+            Some("private-secret")
+        );
+        assert_eq!(
+            npmrc.auth_token_for("https://npm.corp.example/private-other/foo"),
+            Some("corp-secret")
+        );
+        assert_eq!(
+            npmrc.auth_token_for("https://npm.corp.example.evil.net/foo"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expand_env() {
+        let lookup_env = |name: &str| (name == "FOO").then(|| "foo".to_string());
+
+        assert_eq!(expand_env("${FOO}-${BAR}-baz", &lookup_env), "foo--baz");
+        assert_eq!(expand_env("no-vars", &lookup_env), "no-vars");
+        assert_eq!(expand_env("${UNCLOSED", &lookup_env), "${UNCLOSED");
+    }
+}
@@ -0,0 +1,67 @@
+use crate::release::DependencyType;
+
+/// A dependency with a newer version available, as reported by a package manager's `outdated` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutdatedPackage {
+    pub name: String,
+    pub current: String,
+    pub latest: String,
+    /// Names of the workspace projects that depend on this version, if known.
+    pub dependents: Vec<String>,
+    pub dependency_type: Option<DependencyType>,
+}
+
+impl OutdatedPackage {
+    /// Adds this package to `packages`, or merges its dependents into the entry for the same installed version.
+    pub fn merge_into(self, packages: &mut Vec<OutdatedPackage>) {
+        let same_version = packages.iter_mut().find(|package| {
+            package.name == self.name
+                && package.current == self.current
+                && package.latest == self.latest
+        });
+
+        match same_version {
+            Some(package) => {
+                package.dependency_type =
+                    DependencyType::most_important(package.dependency_type, self.dependency_type);
+                for dependent in self.dependents {
+                    if !package.dependents.contains(&dependent) {
+                        package.dependents.push(dependent);
+                    }
+                }
+            }
+            None => packages.push(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outdated_package(current: &str, dependents: &[&str]) -> OutdatedPackage {
+        OutdatedPackage {
+            name: "foo".to_string(),
+            current: current.to_string(),
+            latest: "1.2.0".to_string(),
+            dependents: dependents.iter().map(|d| d.to_string()).collect(),
+            dependency_type: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_into() {
+        let mut packages = Vec::new();
+        outdated_package("1.0.0", &["a"]).merge_into(&mut packages);
+        outdated_package("1.0.0", &["b", "a"]).merge_into(&mut packages);
+        outdated_package("1.1.0", &["c"]).merge_into(&mut packages);
+
+        assert_eq!(
+            packages,
+            vec![
+                outdated_package("1.0.0", &["a", "b"]),
+                outdated_package("1.1.0", &["c"]),
+            ]
+        );
+    }
+}
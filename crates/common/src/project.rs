@@ -0,0 +1,441 @@
+//! Figures out the outdated dependencies of a project from its `package.json` and lockfile, without running a package manager.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+
+use crate::{
+    npm_registry::{Metadata, NpmPackage},
+    npmrc::Npmrc,
+    outdated::OutdatedPackage,
+    release::{DependencyType, PackageError},
+    semver::Semver,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageJson {
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, String>,
+    #[serde(default)]
+    optional_dependencies: BTreeMap<String, String>,
+}
+
+/// The outdated dependencies of a project.
+#[derive(Debug, Default)]
+pub struct OutdatedDependencies {
+    pub packages: Vec<OutdatedPackage>,
+    /// The full registry documents of the outdated packages by name, so that their releases needn't be fetched again
+    pub npm_packages: BTreeMap<String, NpmPackage>,
+    /// The packages that couldn't be fetched, and so may or may not be outdated
+    pub errors: Vec<PackageError>,
+}
+
+/// Lists the dependencies of the project at `path` whose installed version is older than the version tagged `latest` on the registry configured in `npmrc`. Asks the registry about at most `concurrency` packages at a time. A package that fails doesn't stop the others, but is listed with its error.
+pub async fn outdated_packages(
+    npmrc: &Npmrc,
+    path: &Path,
+    concurrency: usize,
+) -> Result<OutdatedDependencies> {
+    let dependencies = read_dependencies(path)?;
+    let installed = read_installed_versions(path)?;
+
+    let package_futures = dependencies
+        .into_iter()
+        .filter_map(|(name, dependency_type)| {
+            let current = installed.get(&name)?.clone();
+            Some(async move {
+                // The latest version is all we need to know here, and the releases of outdated packages fall back to the full document
+                let npm_package = NpmPackage::fetch_with(npmrc, &name, Metadata::Abbreviated)
+                    .await
+                    .map_err(|e| PackageError {
+                        package: name.clone(),
+                        error: format!("{:#}", e),
+                    })?;
+                let package = outdated_package(name, current, &npm_package);
+                Ok(package.map(|package| {
+                    let package = OutdatedPackage {
+                        dependency_type: Some(dependency_type),
+                        ..package
+                    };
+                    (package, npm_package)
+                }))
+            })
+        });
+
+    let results: Vec<Result<Option<(OutdatedPackage, NpmPackage)>, PackageError>> =
+        stream::iter(package_futures)
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+    let mut outdated = OutdatedDependencies::default();
+    for result in results {
+        match result {
+            Ok(Some((package, npm_package))) => {
+                outdated
+                    .npm_packages
+                    .insert(package.name.clone(), npm_package);
+                outdated.packages.push(package);
+            }
+            Ok(None) => {}
+            Err(error) => outdated.errors.push(error),
+        }
+    }
+    outdated.errors.sort();
+
+    Ok(outdated)
+}
+
+fn outdated_package(
+    name: String,
+    current: String,
+    npm_package: &NpmPackage,
+) -> Option<OutdatedPackage> {
+    let latest = npm_package.dist_tags.get("latest")?;
+    let is_outdated = match (current.parse::<Semver>(), latest.parse::<Semver>()) {
+        (Ok(current), Ok(latest)) => current < latest,
+        _ => false,
+    };
+
+    is_outdated.then(|| OutdatedPackage {
+        name,
+        current,
+        latest: latest.clone(),
+        dependents: Vec::new(),
+        dependency_type: None,
+    })
+}
+
+/// Names and types of all dependencies in `package.json` that are installed from the registry.
+fn read_dependencies(path: &Path) -> Result<Vec<(String, DependencyType)>> {
+    let file = path.join("package.json");
+    let content =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let package_json: PackageJson = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("Failed to parse {}: {}", file.display(), e))?;
+
+    let dependencies = [
+        (package_json.dependencies, DependencyType::Prod),
+        (package_json.dev_dependencies, DependencyType::Dev),
+        (package_json.optional_dependencies, DependencyType::Optional),
+    ]
+    .into_iter()
+    .flat_map(|(section, dependency_type)| {
+        section
+            .into_iter()
+            .filter(|(_, range)| is_registry_range(range))
+            .map(move |(name, _)| (name, dependency_type))
+    })
+    .collect();
+
+    Ok(dependencies)
+}
+
+/// Ranges like `workspace:*`, `file:../foo`, `npm:bar@1` or git URLs don't refer to a version of the package itself on the registry.
+fn is_registry_range(range: &str) -> bool {
+    !range.contains(':') && !range.contains('/')
+}
+
+fn read_installed_versions(path: &Path) -> Result<BTreeMap<String, String>> {
+    let Some(lockfile) = find_lockfile(path) else {
+        bail!(
+            "No pnpm-lock.yaml, package-lock.json or npm-shrinkwrap.json found in {}",
+            path.display()
+        );
+    };
+
+    let file = match &lockfile {
+        Lockfile::Pnpm(file) | Lockfile::Npm(file) => file,
+    };
+    let content =
+        fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+
+    let versions = match lockfile {
+        Lockfile::Pnpm(_) => parse_pnpm_lock(&content),
+        Lockfile::Npm(_) => parse_package_lock(&content),
+    };
+    versions.with_context(|| format!("Failed to parse {}", file.display()))
+}
+
+enum Lockfile {
+    Pnpm(PathBuf),
+    Npm(PathBuf),
+}
+
+fn find_lockfile(path: &Path) -> Option<Lockfile> {
+    let pnpm = path.join("pnpm-lock.yaml");
+    if pnpm.is_file() {
+        return Some(Lockfile::Pnpm(pnpm));
+    }
+
+    ["npm-shrinkwrap.json", "package-lock.json"]
+        .into_iter()
+        .map(|name| path.join(name))
+        .find(|file| file.is_file())
+        .map(Lockfile::Npm)
+}
+
+#[derive(Debug, Deserialize)]
+struct PnpmLock {
+    /// Since lockfile v6, in workspaces since v5
+    importers: Option<BTreeMap<String, PnpmImporter>>,
+    #[serde(flatten)]
+    root: PnpmImporter,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PnpmImporter {
+    #[serde(default)]
+    dependencies: BTreeMap<String, PnpmDependency>,
+    #[serde(default)]
+    dev_dependencies: BTreeMap<String, PnpmDependency>,
+    #[serde(default)]
+    optional_dependencies: BTreeMap<String, PnpmDependency>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PnpmDependency {
+    /// Lockfile v5: `foo: 1.2.3`
+    Version(String),
+    /// Lockfile v6+: `foo: { specifier: ^1.2.0, version: 1.2.3 }`
+    Resolved { version: String },
+}
+
+fn parse_pnpm_lock(content: &str) -> Result<BTreeMap<String, String>> {
+    let lock: PnpmLock = serde_yaml::from_str(content)?;
+
+    let root = match lock.importers {
+        Some(mut importers) => importers.remove(".").unwrap_or_default(),
+        None => lock.root,
+    };
+
+    let versions = [
+        root.dependencies,
+        root.dev_dependencies,
+        root.optional_dependencies,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|(name, dependency)| {
+        let version = match dependency {
+            PnpmDependency::Version(version) => version,
+            PnpmDependency::Resolved { version } => version,
+        };
+        (name, strip_peer_suffix(&version).to_string())
+    })
+    .collect();
+
+    Ok(versions)
+}
+
+/// pnpm appends the versions of peer dependencies, e.g. `1.2.3(react@18.2.0)` or `1.2.3_react@18.2.0` in lockfile v5.
+fn strip_peer_suffix(version: &str) -> &str {
+    version.split(['(', '_']).next().unwrap_or(version)
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageLock {
+    /// Since lockfile v2
+    packages: Option<BTreeMap<String, PackageLockEntry>>,
+    /// Lockfile v1
+    #[serde(default)]
+    dependencies: BTreeMap<String, PackageLockEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PackageLockEntry {
+    version: Option<String>,
+}
+
+fn parse_package_lock(content: &str) -> Result<BTreeMap<String, String>> {
+    let lock: PackageLock = serde_json::from_str(content)?;
+
+    let versions = match lock.packages {
+        // Only direct dependencies are installed to the top-level `node_modules`
+        Some(packages) => packages
+            .into_iter()
+            .filter_map(|(path, entry)| {
+                let name = path.strip_prefix("node_modules/")?;
+                (!name.contains("/node_modules/")).then(|| (name.to_string(), entry.version))
+            })
+            .filter_map(|(name, version)| Some((name, version?)))
+            .collect(),
+        None => lock
+            .dependencies
+            .into_iter()
+            .filter_map(|(name, entry)| Some((name, entry.version?)))
+            .collect(),
+    };
+
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestResponse, TestServer};
+
+    #[test]
+    fn test_outdated_packages_lists_failing_packages() {
+        let server = TestServer::start(|req| match req.target.as_str() {
+            "/foo" => TestResponse::new(
+                200,
+                r#"{ "name": "foo", "dist-tags": { "latest": "1.1.0" }, "versions": {} }"#,
+            ),
+            _ => TestResponse::new(404, r#"{"error":"Not found"}"#),
+        });
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(&format!("registry={}", server.url("/")), |_| None);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{ "dependencies": { "foo": "^1.0.0" }, "devDependencies": { "private": "^1.0.0" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("package-lock.json"),
+            r#"{ "lockfileVersion": 3, "packages": {
+                "node_modules/foo": { "version": "1.0.0" },
+                "node_modules/private": { "version": "1.0.0" }
+            } }"#,
+        )
+        .unwrap();
+
+        let outdated = smol::block_on(outdated_packages(&npmrc, dir.path(), 4)).unwrap();
+
+        assert_eq!(outdated.packages.len(), 1);
+        assert_eq!(outdated.packages[0].name, "foo");
+        assert_eq!(outdated.packages[0].latest, "1.1.0");
+        assert!(outdated.npm_packages.contains_key("foo"));
+        assert_eq!(outdated.errors.len(), 1);
+        assert_eq!(outdated.errors[0].package, "private");
+        assert!(outdated.errors[0].error.contains("404"));
+    }
+
+    #[test]
+    fn test_parse_pnpm_lock_v9() {
+        let content = r#"
+lockfileVersion: '9.0'
+
+importers:
+
+  .:
+    dependencies:
+      foo:
+        specifier: ^1.0.0
+        version: 1.2.3
+      react-dom:
+        specifier: ^18.0.0
+        version: 18.2.0(react@18.2.0)
+    devDependencies:
+      '@scope/bar':
+        specifier: ~2.0.0
+        version: 2.0.1
+
+  packages/a:
+    dependencies:
+      baz:
+        specifier: ^3.0.0
+        version: 3.0.0
+"#;
+
+        let versions = parse_pnpm_lock(content).unwrap();
+
+        assert_eq!(
+            versions,
+            BTreeMap::from([
+                ("@scope/bar".to_string(), "2.0.1".to_string()),
+                ("foo".to_string(), "1.2.3".to_string()),
+                ("react-dom".to_string(), "18.2.0".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_pnpm_lock_v5() {
+        let content = r#"
+lockfileVersion: 5.4
+
+specifiers:
+  foo: ^1.0.0
+  react-dom: ^18.0.0
+
+dependencies:
+  foo: 1.2.3
+  react-dom: 18.2.0_react@18.2.0
+"#;
+
+        let versions = parse_pnpm_lock(content).unwrap();
+
+        assert_eq!(
+            versions,
+            BTreeMap::from([
+                ("foo".to_string(), "1.2.3".to_string()),
+                ("react-dom".to_string(), "18.2.0".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_package_lock_v3() {
+        let content = r#"{
+            "lockfileVersion": 3,
+            "packages": {
+                "": { "name": "my-app", "dependencies": { "foo": "^1.0.0" } },
+                "node_modules/foo": { "version": "1.2.3" },
+                "node_modules/@scope/bar": { "version": "2.0.1" },
+                "node_modules/foo/node_modules/baz": { "version": "3.0.0" }
+            }
+        }"#;
+
+        let versions = parse_package_lock(content).unwrap();
+
+        assert_eq!(
+            versions,
+            BTreeMap::from([
+                ("@scope/bar".to_string(), "2.0.1".to_string()),
+                ("foo".to_string(), "1.2.3".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_package_lock_v1() {
+        let content = r#"{
+            "lockfileVersion": 1,
+            "dependencies": {
+                "foo": { "version": "1.2.3" }
+            }
+        }"#;
+
+        let versions = parse_package_lock(content).unwrap();
+
+        assert_eq!(
+            versions,
+            BTreeMap::from([("foo".to_string(), "1.2.3".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_is_registry_range() {
+        assert!(is_registry_range("^1.0.0"));
+        assert!(is_registry_range("latest"));
+        assert!(!is_registry_range("workspace:*"));
+        assert!(!is_registry_range("file:../foo"));
+        assert!(!is_registry_range("npm:bar@^1.0.0"));
+        assert!(!is_registry_range("github:owner/repo"));
+        assert!(!is_registry_range("owner/repo"));
+    }
+}
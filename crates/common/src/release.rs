@@ -1,20 +1,225 @@
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Eq, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+use crate::semver::Semver;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Release {
     pub package: String,
     pub semver: String,
-    pub repository_url: String,
+    /// The web URL of the repository, e.g. `https://github.com/owner/repo`, if the package tells.
+    #[serde(default)]
+    pub repository_url: Option<String>,
+    #[serde(default)]
+    pub kind: UpdateKind,
+    /// Names of the workspace projects that depend on the package, if known.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependency_type: Option<DependencyType>,
+    /// The package's path inside its repository, if it lives in a monorepo.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// The commit the release was published from, if the registry knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_head: Option<String>,
+    /// The URL of the package's tarball on the registry, if the registry tells.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tarball_url: Option<String>,
+}
+
+/// A package whose releases could not be fetched, e.g. because the registry doesn't know it.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
+pub struct PackageError {
+    pub package: String,
+    pub error: String,
+}
+
+impl std::fmt::Display for PackageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.package, self.error)
+    }
+}
+
+/// What there is to review for a package: one of its releases, or the reason why we have none.
+///
+/// Lists of releases are serialized as lists of entries, so that failures reach the reviewer instead of vanishing.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum ReleaseEntry {
+    Release(Release),
+    Error(PackageError),
+}
+
+impl ReleaseEntry {
+    pub fn package(&self) -> &str {
+        match self {
+            ReleaseEntry::Release(release) => &release.package,
+            ReleaseEntry::Error(error) => &error.package,
+        }
+    }
+
+    pub fn as_release(&self) -> Option<&Release> {
+        match self {
+            ReleaseEntry::Release(release) => Some(release),
+            ReleaseEntry::Error(_) => None,
+        }
+    }
+
+    pub fn as_error(&self) -> Option<&PackageError> {
+        match self {
+            ReleaseEntry::Release(_) => None,
+            ReleaseEntry::Error(error) => Some(error),
+        }
+    }
+}
+
+/// Entries are sorted by package, with a package's error before its releases.
+impl Ord for ReleaseEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (ReleaseEntry::Release(a), ReleaseEntry::Release(b)) => a.cmp(b),
+            (ReleaseEntry::Error(a), ReleaseEntry::Error(b)) => a.cmp(b),
+            (ReleaseEntry::Error(_), ReleaseEntry::Release(_)) => self
+                .package()
+                .cmp(other.package())
+                .then(std::cmp::Ordering::Less),
+            (ReleaseEntry::Release(_), ReleaseEntry::Error(_)) => self
+                .package()
+                .cmp(other.package())
+                .then(std::cmp::Ordering::Greater),
+        }
+    }
+}
+
+impl PartialOrd for ReleaseEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// How big a step a release is compared to the currently installed version.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateKind {
+    Patch,
+    #[default]
+    Minor,
+    Major,
+}
+
+impl std::fmt::Display for UpdateKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateKind::Patch => write!(f, "patch"),
+            UpdateKind::Minor => write!(f, "minor"),
+            UpdateKind::Major => write!(f, "major"),
+        }
+    }
+}
+
+impl std::str::FromStr for UpdateKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "patch" => Ok(UpdateKind::Patch),
+            "minor" => Ok(UpdateKind::Minor),
+            "major" => Ok(UpdateKind::Major),
+            _ => Err(anyhow::anyhow!("unknown update kind: {}", s)),
+        }
+    }
+}
+
+/// Which section of `package.json` a dependency is declared in.
+///
+/// The variant order matters: if a package is declared in several sections, e.g. by different workspace projects, the first one is what counts.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyType {
+    Prod,
+    Optional,
+    Peer,
+    Dev,
+}
+
+impl DependencyType {
+    /// Maps the name of a `package.json` section, e.g. `devDependencies`, as printed by package managers.
+    pub fn from_section(section: &str) -> Option<DependencyType> {
+        match section {
+            "dependencies" => Some(DependencyType::Prod),
+            "optionalDependencies" => Some(DependencyType::Optional),
+            "peerDependencies" => Some(DependencyType::Peer),
+            "devDependencies" => Some(DependencyType::Dev),
+            _ => None,
+        }
+    }
+
+    /// The more important of two possibly unknown dependency types.
+    pub fn most_important(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl std::fmt::Display for DependencyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyType::Prod => write!(f, "prod"),
+            DependencyType::Optional => write!(f, "optional"),
+            DependencyType::Peer => write!(f, "peer"),
+            DependencyType::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+impl std::str::FromStr for DependencyType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prod" => Ok(DependencyType::Prod),
+            "optional" => Ok(DependencyType::Optional),
+            "peer" => Ok(DependencyType::Peer),
+            "dev" => Ok(DependencyType::Dev),
+            _ => Err(anyhow::anyhow!("unknown dependency type: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Release {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.package, self.semver)
+    }
 }
 
-impl ToString for Release {
-    fn to_string(&self) -> String {
-        format!("{}@{}", self.package, self.semver)
+impl Release {
+    /// Whether both releases belong to the same `major.minor` line of the same package, e.g. `foo@1.3.0` and `foo@1.3.2`.
+    pub fn is_same_minor_line(&self, other: &Release) -> bool {
+        match (
+            self.semver.parse::<Semver>(),
+            other.semver.parse::<Semver>(),
+        ) {
+            (Ok(a), Ok(b)) => self.package == other.package && a.is_same_minor_line(&b),
+            _ => false,
+        }
     }
 }
 
 impl Ord for Release {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         match self.package.cmp(&other.package) {
-            std::cmp::Ordering::Equal => self.semver.cmp(&other.semver),
+            // Compare by precedence so that `1.10.0` comes after `1.9.0`, and fall back to comparing strings for unparsable versions
+            std::cmp::Ordering::Equal => {
+                match (
+                    self.semver.parse::<Semver>(),
+                    other.semver.parse::<Semver>(),
+                ) {
+                    (Ok(a), Ok(b)) => a.cmp(&b).then_with(|| self.semver.cmp(&other.semver)),
+                    _ => self.semver.cmp(&other.semver),
+                }
+            }
             other => other,
         }
     }
@@ -25,3 +230,98 @@ impl PartialOrd for Release {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(package: &str, semver: &str) -> Release {
+        Release {
+            package: package.to_string(),
+            semver: semver.to_string(),
+            repository_url: Some("https://github.com/foo/foo".to_string()),
+            kind: UpdateKind::Minor,
+            dependents: Vec::new(),
+            dependency_type: None,
+            directory: None,
+            git_head: None,
+            tarball_url: None,
+        }
+    }
+
+    #[test]
+    fn test_releases_are_sorted_by_package_and_precedence() {
+        let mut releases = [
+            release("foo", "1.10.0"),
+            release("bar", "2.0.0"),
+            release("foo", "1.9.1"),
+            release("foo", "1.9.0"),
+            release("foo", "1.10.0-beta.1"),
+        ];
+        releases.sort();
+
+        let sorted: Vec<String> = releases.iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            sorted,
+            vec![
+                "bar@2.0.0",
+                "foo@1.9.0",
+                "foo@1.9.1",
+                "foo@1.10.0-beta.1",
+                "foo@1.10.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_release_entries_roundtrip() {
+        let entries = vec![
+            ReleaseEntry::Release(release("foo", "1.1.0")),
+            ReleaseEntry::Error(PackageError {
+                package: "bar".to_string(),
+                error: "NPM registry error for bar: 404".to_string(),
+            }),
+        ];
+
+        let json = serde_json::to_string(&entries).unwrap();
+        let parsed: Vec<ReleaseEntry> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed, entries);
+    }
+
+    #[test]
+    fn test_release_entries_list_errors_first() {
+        let error = |package: &str| {
+            ReleaseEntry::Error(PackageError {
+                package: package.to_string(),
+                error: "oops".to_string(),
+            })
+        };
+        let mut entries = [
+            ReleaseEntry::Release(release("foo", "1.1.0")),
+            error("foo"),
+            ReleaseEntry::Release(release("bar", "2.1.0")),
+            error("baz"),
+        ];
+        entries.sort();
+
+        let packages: Vec<String> = entries
+            .iter()
+            .map(|entry| match entry {
+                ReleaseEntry::Release(release) => release.to_string(),
+                ReleaseEntry::Error(error) => error.to_string(),
+            })
+            .collect();
+        assert_eq!(
+            packages,
+            ["bar@2.1.0", "baz: oops", "foo: oops", "foo@1.1.0"]
+        );
+    }
+
+    #[test]
+    fn test_is_same_minor_line() {
+        assert!(release("foo", "1.3.0").is_same_minor_line(&release("foo", "1.3.2")));
+        assert!(!release("foo", "1.3.0").is_same_minor_line(&release("foo", "1.4.0")));
+        assert!(!release("foo", "1.3.0").is_same_minor_line(&release("bar", "1.3.2")));
+    }
+}
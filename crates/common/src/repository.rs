@@ -0,0 +1,164 @@
+use serde::{de::IgnoredAny, Deserialize};
+use url::Url;
+
+/// The `repository` field of a `package.json`, in any of its documented shapes.
+///
+/// See https://docs.npmjs.com/cli/v11/configuring-npm/package-json#repository
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Repository {
+    /// `"github:owner/repo"`, `"owner/repo"`, or a plain URL
+    Shorthand(String),
+    Object {
+        url: String,
+        /// The package's path inside a monorepo
+        #[serde(default)]
+        directory: Option<String>,
+    },
+    /// Anything else is malformed, but must not fail the whole package.
+    Unknown(IgnoredAny),
+}
+
+impl Repository {
+    pub fn web_url(&self) -> Option<String> {
+        match self {
+            Repository::Shorthand(spec) | Repository::Object { url: spec, .. } => web_url(spec),
+            Repository::Unknown(_) => None,
+        }
+    }
+
+    pub fn directory(&self) -> Option<&str> {
+        match self {
+            Repository::Object { directory, .. } => directory.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Normalizes a repository shorthand or git URL to the web URL of the repository, e.g. `https://github.com/owner/repo`.
+///
+/// Accepts the shorthands `owner/repo`, `github:owner/repo`, `gitlab:owner/repo`, `bitbucket:owner/repo` and `gist:id`, SCP-like `git@host:owner/repo.git`, and URLs with the schemes `https`, `http`, `git`, `ssh`, `git+https`, `git+http` and `git+ssh`.
+pub fn web_url(spec: &str) -> Option<String> {
+    let spec = spec.trim();
+
+    if let Some((prefix, path)) = spec.split_once(':') {
+        let host = match prefix {
+            "github" => Some("github.com"),
+            "gitlab" => Some("gitlab.com"),
+            "bitbucket" => Some("bitbucket.org"),
+            "gist" => Some("gist.github.com"),
+            _ => None,
+        };
+        if let Some(host) = host {
+            return format_web_url(host, path);
+        }
+    }
+
+    if !spec.contains(':') {
+        return match spec.split('/').count() {
+            2 => format_web_url("github.com", spec),
+            _ => None,
+        };
+    }
+
+    if !spec.contains("://") {
+        // SCP-like syntax, e.g. `git@github.com:owner/repo.git`
+        let (user_and_host, path) = spec.split_once(':')?;
+        let (_, host) = user_and_host.split_once('@')?;
+        return format_web_url(host, path);
+    }
+
+    let url = Url::parse(spec.strip_prefix("git+").unwrap_or(spec)).ok()?;
+    match url.scheme() {
+        "https" | "http" | "git" | "ssh" => format_web_url(url.host_str()?, url.path()),
+        _ => None,
+    }
+}
+
+fn format_web_url(host: &str, path: &str) -> Option<String> {
+    let path = path.split(['#', '?']).next().unwrap_or_default();
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+
+    Some(format!("https://{}/{}", host, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_url_of_shorthands() {
+        for (spec, expected) in [
+            ("owner/repo", "https://github.com/owner/repo"),
+            ("github:owner/repo", "https://github.com/owner/repo"),
+            ("github:owner/repo#v1.0.0", "https://github.com/owner/repo"),
+            ("gitlab:owner/repo", "https://gitlab.com/owner/repo"),
+            ("bitbucket:owner/repo", "https://bitbucket.org/owner/repo"),
+            ("gist:11081aaa281", "https://gist.github.com/11081aaa281"),
+        ] {
+            assert_eq!(web_url(spec).as_deref(), Some(expected), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_web_url_of_urls() {
+        for spec in [
+            "https://github.com/owner/repo",
+            "https://github.com/owner/repo/",
+            "https://github.com/owner/repo.git",
+            "https://github.com/owner/repo#readme",
+            "http://github.com/owner/repo",
+            "git+https://github.com/owner/repo.git",
+            "git://github.com/owner/repo.git",
+            "git+ssh://git@github.com/owner/repo.git",
+            "ssh://git@github.com/owner/repo.git",
+            "git@github.com:owner/repo.git",
+        ] {
+            assert_eq!(
+                web_url(spec).as_deref(),
+                Some("https://github.com/owner/repo"),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_web_url_keeps_nested_groups() {
+        assert_eq!(
+            web_url("git+https://gitlab.com/group/subgroup/repo.git").as_deref(),
+            Some("https://gitlab.com/group/subgroup/repo")
+        );
+    }
+
+    #[test]
+    fn test_web_url_rejects_garbage() {
+        for spec in ["", "repo", "file:../repo", "https://", "a/b/c"] {
+            assert_eq!(web_url(spec), None, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_any_shape() {
+        let web_url_of = |json: &str| serde_json::from_str::<Repository>(json).unwrap().web_url();
+
+        assert_eq!(
+            web_url_of(r#""github:owner/repo""#).as_deref(),
+            Some("https://github.com/owner/repo")
+        );
+        assert_eq!(
+            web_url_of(
+                r#"{ "type": "git", "url": "git+https://github.com/owner/repo.git", "directory": "packages/foo" }"#
+            )
+            .as_deref(),
+            Some("https://github.com/owner/repo")
+        );
+        assert_eq!(web_url_of(r#"{ "type": "git" }"#), None);
+        assert_eq!(web_url_of("42"), None);
+    }
+}
@@ -1,25 +1,41 @@
-use std::fmt;
+use std::{cmp::Ordering, fmt};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+use crate::release::UpdateKind;
+
+/// A version as specified by [SemVer 2.0](https://semver.org/spec/v2.0.0.html), including optional pre-release identifiers and build metadata.
+#[derive(Debug, Clone)]
 pub struct Semver {
     major: u32,
     minor: u32,
     patch: u32,
+    pre: Vec<Identifier>,
+    build: Vec<String>,
+}
+
+/// A dot-separated pre-release identifier, e.g. `beta` or `1` in `1.0.0-beta.1`.
+///
+/// The variant order matters: numeric identifiers always have lower precedence than alphanumeric ones.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Identifier {
+    Numeric(u64),
+    AlphaNumeric(String),
 }
 
 #[derive(Debug, PartialEq)]
 pub enum SemverParsingError {
-    Prerelease,
     InvalidFormat,
     InvalidNumber(&'static str),
+    InvalidPrerelease,
+    InvalidBuild,
 }
 
 impl fmt::Display for SemverParsingError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            SemverParsingError::Prerelease => write!(f, "pre-release versions are not supported"),
             SemverParsingError::InvalidFormat => write!(f, "invalid semver format"),
             SemverParsingError::InvalidNumber(part) => write!(f, "invalid {} number", part),
+            SemverParsingError::InvalidPrerelease => write!(f, "invalid pre-release identifier"),
+            SemverParsingError::InvalidBuild => write!(f, "invalid build metadata"),
         }
     }
 }
@@ -30,12 +46,19 @@ impl std::str::FromStr for Semver {
     type Err = SemverParsingError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Handle pre-release versions like "1.0.0-alpha"
-        if s.contains('-') {
-            return Err(SemverParsingError::Prerelease);
-        }
+        // Split off build metadata first, since it may contain hyphens, e.g. "1.0.0+build-5"
+        let (s, build) = match s.split_once('+') {
+            Some((s, build)) => (s, parse_build(build)?),
+            None => (s, Vec::new()),
+        };
 
-        let parts: Vec<&str> = s.split('.').collect();
+        // The pre-release starts at the first hyphen, e.g. "1.0.0-alpha-1"
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) => (core, parse_prerelease(pre)?),
+            None => (s, Vec::new()),
+        };
+
+        let parts: Vec<&str> = core.split('.').collect();
         if parts.len() != 3 {
             return Err(SemverParsingError::InvalidFormat);
         }
@@ -54,19 +77,180 @@ impl std::str::FromStr for Semver {
             major,
             minor,
             patch,
+            pre,
+            build,
         })
     }
 }
 
+fn parse_prerelease(s: &str) -> Result<Vec<Identifier>, SemverParsingError> {
+    s.split('.')
+        .map(|identifier| {
+            if !is_valid_identifier(identifier) {
+                return Err(SemverParsingError::InvalidPrerelease);
+            }
+
+            if !identifier.bytes().all(|b| b.is_ascii_digit()) {
+                return Ok(Identifier::AlphaNumeric(identifier.to_string()));
+            }
+
+            // Numeric identifiers must not include leading zeroes
+            if identifier.len() > 1 && identifier.starts_with('0') {
+                return Err(SemverParsingError::InvalidPrerelease);
+            }
+
+            identifier
+                .parse()
+                .map(Identifier::Numeric)
+                .map_err(|_| SemverParsingError::InvalidPrerelease)
+        })
+        .collect()
+}
+
+fn parse_build(s: &str) -> Result<Vec<String>, SemverParsingError> {
+    s.split('.')
+        .map(|identifier| match is_valid_identifier(identifier) {
+            true => Ok(identifier.to_string()),
+            false => Err(SemverParsingError::InvalidBuild),
+        })
+        .collect()
+}
+
+fn is_valid_identifier(identifier: &str) -> bool {
+    !identifier.is_empty()
+        && identifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
 impl fmt::Display for Semver {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if !self.pre.is_empty() {
+            let pre: Vec<String> = self.pre.iter().map(|i| i.to_string()).collect();
+            write!(f, "-{}", pre.join("."))?;
+        }
+
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build.join("."))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Identifier::Numeric(n) => write!(f, "{}", n),
+            Identifier::AlphaNumeric(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Compares by precedence, i.e. build metadata is ignored.
+impl Ord for Semver {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.major
+            .cmp(&other.major)
+            .then(self.minor.cmp(&other.minor))
+            .then(self.patch.cmp(&other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                // A pre-release has lower precedence than its normal version
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre.cmp(&other.pre),
+            })
+    }
+}
+
+impl PartialOrd for Semver {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
+impl PartialEq for Semver {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Semver {}
+
 impl Semver {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Semver {
+        Semver {
+            major,
+            minor,
+            patch,
+            pre: Vec::new(),
+            build: Vec::new(),
+        }
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
     pub fn is_minor_update_of(&self, other: &Semver) -> bool {
-        self.major == other.major && self.minor > other.minor && self.patch == 0
+        self.update_kind_of(other) == Some(UpdateKind::Minor) && self.starts(UpdateKind::Minor)
+    }
+
+    /// The kind of update from `other` to this version, or `None` if this version is not newer.
+    ///
+    /// Steps along a pre-release track, e.g. from `2.0.0-beta.1` to `2.0.0-beta.2` or `2.0.0`, are of the kind of the version they lead to.
+    pub fn update_kind_of(&self, other: &Semver) -> Option<UpdateKind> {
+        if self <= other {
+            return None;
+        }
+
+        let kind = if self.major != other.major {
+            UpdateKind::Major
+        } else if self.minor != other.minor {
+            UpdateKind::Minor
+        } else if self.patch != other.patch {
+            UpdateKind::Patch
+        } else if self.minor == 0 && self.patch == 0 {
+            UpdateKind::Major
+        } else if self.patch == 0 {
+            UpdateKind::Minor
+        } else {
+            UpdateKind::Patch
+        };
+
+        Some(kind)
+    }
+
+    /// Whether this is the first version of a series of the given kind, e.g. `2.0.0` for major or `2.1.0` for minor updates. Every version starts a patch.
+    pub fn starts(&self, kind: UpdateKind) -> bool {
+        match kind {
+            UpdateKind::Major => self.minor == 0 && self.patch == 0,
+            UpdateKind::Minor => self.patch == 0,
+            UpdateKind::Patch => true,
+        }
+    }
+
+    /// Whether both versions belong to the same `major.minor` line, e.g. `1.3.0` and `1.3.2`.
+    pub fn is_same_minor_line(&self, other: &Semver) -> bool {
+        self.major == other.major && self.minor == other.minor
+    }
+
+    /// Whether this is a patch of the version starting a series of the given kind, e.g. `2.0.1` for major updates, but not `2.1.0`.
+    pub fn is_patch_of_start(&self, kind: UpdateKind) -> bool {
+        match kind {
+            UpdateKind::Major => self.minor == 0,
+            UpdateKind::Minor | UpdateKind::Patch => true,
+        }
+    }
+
+    /// Whether this is a later step on the way to the pre-release `other` is on, e.g. `2.0.0-beta.2` or `2.0.0` for `2.0.0-beta.1`.
+    pub fn is_prerelease_update_of(&self, other: &Semver) -> bool {
+        other.is_prerelease()
+            && (self.major, self.minor, self.patch) == (other.major, other.minor, other.patch)
+            && self > other
     }
 
     pub fn is_at_most(&self, max: &Semver) -> bool {
@@ -80,104 +264,48 @@ mod tests {
 
     #[test]
     fn test_semver_equality() {
-        let v1 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 3,
-        };
-        let v2 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 3,
-        };
+        let v1 = Semver::new(1, 2, 3);
+        let v2 = Semver::new(1, 2, 3);
         assert_eq!(v1, v2);
     }
 
     #[test]
     fn test_semver_inequality() {
-        let v1 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 3,
-        };
-        let v2 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 4,
-        };
+        let v1 = Semver::new(1, 2, 3);
+        let v2 = Semver::new(1, 2, 4);
         assert_ne!(v1, v2);
     }
 
     #[test]
     fn test_semver_major_comparison() {
-        let v1 = Semver {
-            major: 1,
-            minor: 0,
-            patch: 0,
-        };
-        let v2 = Semver {
-            major: 2,
-            minor: 0,
-            patch: 0,
-        };
+        let v1 = Semver::new(1, 0, 0);
+        let v2 = Semver::new(2, 0, 0);
         assert!(v1 < v2);
         assert!(v2 > v1);
     }
 
     #[test]
     fn test_semver_minor_comparison() {
-        let v1 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 0,
-        };
-        let v2 = Semver {
-            major: 1,
-            minor: 3,
-            patch: 0,
-        };
+        let v1 = Semver::new(1, 2, 0);
+        let v2 = Semver::new(1, 3, 0);
         assert!(v1 < v2);
         assert!(v2 > v1);
     }
 
     #[test]
     fn test_semver_patch_comparison() {
-        let v1 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 3,
-        };
-        let v2 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 4,
-        };
+        let v1 = Semver::new(1, 2, 3);
+        let v2 = Semver::new(1, 2, 4);
         assert!(v1 < v2);
         assert!(v2 > v1);
     }
 
     #[test]
     fn test_semver_complex_comparison() {
-        let v1 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 3,
-        };
-        let v2 = Semver {
-            major: 1,
-            minor: 2,
-            patch: 4,
-        };
-        let v3 = Semver {
-            major: 1,
-            minor: 3,
-            patch: 0,
-        };
-        let v4 = Semver {
-            major: 2,
-            minor: 0,
-            patch: 0,
-        };
+        let v1 = Semver::new(1, 2, 3);
+        let v2 = Semver::new(1, 2, 4);
+        let v3 = Semver::new(1, 3, 0);
+        let v4 = Semver::new(2, 0, 0);
 
         assert!(v1 < v2);
         assert!(v2 < v3);
@@ -195,11 +323,190 @@ mod tests {
         assert_eq!(semver.patch, 3);
     }
 
+    #[test]
+    fn test_from_str_valid_prerelease() {
+        let semver: Semver = "1.2.3-beta.1".parse().unwrap();
+        assert_eq!(semver.major, 1);
+        assert_eq!(semver.minor, 2);
+        assert_eq!(semver.patch, 3);
+        assert_eq!(
+            semver.pre,
+            vec![
+                Identifier::AlphaNumeric("beta".to_string()),
+                Identifier::Numeric(1)
+            ]
+        );
+        assert!(semver.is_prerelease());
+    }
+
+    #[test]
+    fn test_from_str_valid_build_metadata() {
+        let semver: Semver = "1.2.3-rc-1+build.5-a".parse().unwrap();
+        assert_eq!(
+            semver.pre,
+            vec![Identifier::AlphaNumeric("rc-1".to_string())]
+        );
+        assert_eq!(semver.build, vec!["build", "5-a"]);
+    }
+
     #[test]
     fn test_from_str_invalid_prerelease() {
-        let result: Result<Semver, _> = "1.2.3-beta.1".parse();
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), SemverParsingError::Prerelease);
+        let invalid_cases = vec!["1.2.3-", "1.2.3-beta..1", "1.2.3-beta.01", "1.2.3-bet@"];
+
+        for version in invalid_cases {
+            let result: Result<Semver, _> = version.parse();
+            assert_eq!(
+                result.unwrap_err(),
+                SemverParsingError::InvalidPrerelease,
+                "Should fail for version: {}",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_str_invalid_build_metadata() {
+        let result: Result<Semver, _> = "1.2.3+".parse();
+        assert_eq!(result.unwrap_err(), SemverParsingError::InvalidBuild);
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        for version in [
+            "1.2.3",
+            "1.2.3-alpha.1",
+            "1.2.3+build.1",
+            "1.2.3-0.rc-1+sha.5114f85",
+        ] {
+            let semver: Semver = version.parse().unwrap();
+            assert_eq!(semver.to_string(), version);
+        }
+    }
+
+    #[test]
+    fn test_prerelease_precedence() {
+        // Example from https://semver.org/spec/v2.0.0.html#spec-item-11
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+        ];
+
+        for pair in ordered.windows(2) {
+            let lower: Semver = pair[0].parse().unwrap();
+            let greater: Semver = pair[1].parse().unwrap();
+            assert!(
+                lower < greater,
+                "{} should be less than {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_metadata_is_ignored_for_precedence() {
+        let v1: Semver = "1.0.0+build.1".parse().unwrap();
+        let v2: Semver = "1.0.0+build.2".parse().unwrap();
+        assert_eq!(v1, v2);
+    }
+
+    #[test]
+    fn test_update_kind_of() {
+        let current: Semver = "1.2.3".parse().unwrap();
+
+        let cases = [
+            ("1.2.4", Some(UpdateKind::Patch)),
+            ("1.3.0", Some(UpdateKind::Minor)),
+            ("1.3.1", Some(UpdateKind::Minor)),
+            ("2.0.0", Some(UpdateKind::Major)),
+            ("1.2.3", None),
+            ("1.2.2", None),
+        ];
+
+        for (version, kind) in cases {
+            let semver: Semver = version.parse().unwrap();
+            assert_eq!(semver.update_kind_of(&current), kind, "{}", version);
+        }
+    }
+
+    #[test]
+    fn test_update_kind_of_prerelease_track() {
+        let current: Semver = "2.0.0-beta.1".parse().unwrap();
+
+        let cases = [
+            ("2.0.0-beta.2", Some(UpdateKind::Major)),
+            ("2.0.0-rc.1", Some(UpdateKind::Major)),
+            ("2.0.0", Some(UpdateKind::Major)),
+            ("2.0.0-alpha.1", None),
+            ("2.0.0-beta.1", None),
+        ];
+
+        for (version, kind) in cases {
+            let semver: Semver = version.parse().unwrap();
+            assert_eq!(semver.update_kind_of(&current), kind, "{}", version);
+        }
+    }
+
+    #[test]
+    fn test_is_patch_of_start() {
+        let cases = [
+            ("2.0.0", UpdateKind::Major, true),
+            ("2.0.3", UpdateKind::Major, true),
+            ("2.1.0", UpdateKind::Major, false),
+            ("1.3.2", UpdateKind::Minor, true),
+            ("1.2.4", UpdateKind::Patch, true),
+        ];
+
+        for (version, kind, expected) in cases {
+            let semver: Semver = version.parse().unwrap();
+            assert_eq!(
+                semver.is_patch_of_start(kind),
+                expected,
+                "{} {:?}",
+                version,
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_prerelease_update_of() {
+        let current: Semver = "2.0.0-beta.1".parse().unwrap();
+
+        let updates = ["2.0.0-beta.2", "2.0.0-rc.1", "2.0.0"];
+        for version in updates {
+            let semver: Semver = version.parse().unwrap();
+            assert!(semver.is_prerelease_update_of(&current), "{}", version);
+        }
+
+        let non_updates = ["2.0.0-alpha.1", "2.0.0-beta.1", "2.0.1-beta.1", "2.1.0"];
+        for version in non_updates {
+            let semver: Semver = version.parse().unwrap();
+            assert!(!semver.is_prerelease_update_of(&current), "{}", version);
+        }
+    }
+
+    #[test]
+    fn test_is_minor_update_of() {
+        let current: Semver = "1.2.3".parse().unwrap();
+
+        let minor_updates = ["1.3.0", "1.10.0", "1.3.0-beta.1"];
+        for version in minor_updates {
+            let semver: Semver = version.parse().unwrap();
+            assert!(semver.is_minor_update_of(&current), "{}", version);
+        }
+
+        let other_updates = ["1.2.4", "1.3.1", "2.0.0", "1.1.0"];
+        for version in other_updates {
+            let semver: Semver = version.parse().unwrap();
+            assert!(!semver.is_minor_update_of(&current), "{}", version);
+        }
     }
 
     #[test]
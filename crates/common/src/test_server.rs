@@ -0,0 +1,260 @@
+//! A tiny HTTP/1.1 server on localhost, standing in for the registry, GitHub and friends in tests. And a proxy to reach it through.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::async_h1_client::cache::{self, HttpCache};
+
+#[derive(Debug, Clone)]
+pub struct TestRequest {
+    pub method: String,
+    /// The request target as sent, e.g. `/@scope%2fname?foo=bar`
+    pub target: String,
+    /// Header names are lowercased.
+    pub headers: BTreeMap<String, String>,
+    /// Counts the connections the server accepted, starting at 0.
+    pub connection: usize,
+}
+
+impl TestRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> TestResponse {
+        TestResponse {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> TestResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&TestRequest) -> TestResponse + Send + Sync;
+
+pub struct TestServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<TestRequest>>>,
+    response_bytes: Arc<Mutex<usize>>,
+}
+
+impl TestServer {
+    /// Serves every request with `handler` until the test process exits.
+    ///
+    /// Disables the HTTP cache of [`crate::async_h1_client::fetch_cached`] as well, so that tests neither read nor litter the user's cache.
+    pub fn start(
+        handler: impl Fn(&TestRequest) -> TestResponse + Send + Sync + 'static,
+    ) -> TestServer {
+        cache::init(HttpCache::disabled());
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind test server");
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let response_bytes = Arc::new(Mutex::new(0));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let server = TestServer {
+            addr,
+            requests: Arc::clone(&requests),
+            response_bytes: Arc::clone(&response_bytes),
+        };
+
+        thread::spawn(move || {
+            for (connection, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { continue };
+                let handler = Arc::clone(&handler);
+                let requests = Arc::clone(&requests);
+                let response_bytes = Arc::clone(&response_bytes);
+                thread::spawn(move || {
+                    serve_connection(stream, connection, &*handler, &requests, &response_bytes)
+                });
+            }
+        });
+
+        server
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The URL of `path` on this server, e.g. `http://127.0.0.1:1234/foo` for `/foo`.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    pub fn requests(&self) -> Vec<TestRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The number of bytes sent in responses so far, including heads.
+    pub fn response_bytes(&self) -> usize {
+        *self.response_bytes.lock().unwrap()
+    }
+}
+
+/// Serves requests on one connection until the client closes it, so that clients may reuse it.
+fn serve_connection(
+    stream: TcpStream,
+    connection: usize,
+    handler: &Handler,
+    requests: &Mutex<Vec<TestRequest>>,
+    response_bytes: &Mutex<usize>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    while let Some(request) = read_request(&mut reader, connection) {
+        requests.lock().unwrap().push(request.clone());
+        let response = handler(&request);
+
+        let mut head = format!("HTTP/1.1 {} Test\r\n", response.status);
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", response.body.len()));
+
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&response.body);
+        if writer.write_all(&bytes).is_err() {
+            return;
+        }
+        *response_bytes.lock().unwrap() += bytes.len();
+
+        let closes = response
+            .headers
+            .iter()
+            .any(|(name, value)| name.eq_ignore_ascii_case("connection") && value == "close");
+        if closes {
+            return;
+        }
+    }
+}
+
+/// A forward proxy on localhost that tunnels `CONNECT` requests, standing in for corporate proxies in tests.
+pub struct TestProxy {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<TestRequest>>>,
+}
+
+impl TestProxy {
+    pub fn start() -> TestProxy {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind test proxy");
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let proxy = TestProxy {
+            addr,
+            requests: Arc::clone(&requests),
+        };
+
+        thread::spawn(move || {
+            for (connection, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { continue };
+                let requests = Arc::clone(&requests);
+                thread::spawn(move || tunnel_connection(stream, connection, &requests));
+            }
+        });
+
+        proxy
+    }
+
+    /// The URL to configure the proxy with, e.g. `http://127.0.0.1:1234`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// The `CONNECT` requests so far, whose target is the tunnel's `host:port`.
+    pub fn requests(&self) -> Vec<TestRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn tunnel_connection(stream: TcpStream, connection: usize, requests: &Mutex<Vec<TestRequest>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+
+    let Some(request) = read_request(&mut reader, connection) else {
+        return;
+    };
+    requests.lock().unwrap().push(request.clone());
+
+    let upstream = match request.method.as_str() {
+        "CONNECT" => TcpStream::connect(&request.target),
+        _ => Err(io::ErrorKind::Unsupported.into()),
+    };
+    let Ok(mut upstream) = upstream else {
+        writer.write_all(b"HTTP/1.1 502 Bad Gateway\r\n\r\n").ok();
+        return;
+    };
+    if writer
+        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+        .is_err()
+    {
+        return;
+    }
+
+    let mut upstream_writer = upstream.try_clone().unwrap();
+    thread::spawn(move || {
+        io::copy(&mut reader, &mut upstream_writer).ok();
+        upstream_writer.shutdown(Shutdown::Write).ok();
+    });
+    io::copy(&mut upstream, &mut writer).ok();
+    writer.shutdown(Shutdown::Write).ok();
+}
+
+fn read_request(reader: &mut impl BufRead, connection: usize) -> Option<TestRequest> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+
+    let mut headers = BTreeMap::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let content_length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(TestRequest {
+        method,
+        target,
+        headers,
+        connection,
+    })
+}
@@ -0,0 +1,3 @@
+pub mod npm;
+
+pub use crate::npm::{parse, parse_input, NpmOutdatedEntry, NpmOutdatedOutput};
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use common::{outdated::OutdatedPackage, release::DependencyType};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct NpmOutdatedEntry {
+    /// Missing if the package is not installed.
+    pub current: Option<String>,
+    pub wanted: String,
+    pub latest: String,
+    pub dependent: Option<String>,
+    pub location: Option<String>,
+    /// The `package.json` section, e.g. `devDependencies`. Only with `--long`
+    pub r#type: Option<String>,
+}
+
+/// In workspaces, npm lists a package once per workspace that depends on it.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum NpmOutdatedEntries {
+    One(NpmOutdatedEntry),
+    Many(Vec<NpmOutdatedEntry>),
+}
+
+pub type NpmOutdatedOutput = BTreeMap<String, NpmOutdatedEntries>;
+
+pub fn parse_input() -> Result<Vec<OutdatedPackage>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    parse(&input)
+}
+
+/// Parses the output of `npm outdated --json`.
+pub fn parse(input: &str) -> Result<Vec<OutdatedPackage>> {
+    let output: NpmOutdatedOutput = serde_json::from_str(input)?;
+
+    let mut packages = Vec::new();
+    for (name, entries) in output {
+        let entries = match entries {
+            NpmOutdatedEntries::One(entry) => vec![entry],
+            NpmOutdatedEntries::Many(entries) => entries,
+        };
+
+        for entry in entries {
+            // There is nothing to review for packages that are not installed
+            let Some(current) = entry.current else {
+                continue;
+            };
+
+            OutdatedPackage {
+                name: name.clone(),
+                current,
+                latest: entry.latest,
+                dependents: entry.dependent.into_iter().collect(),
+                dependency_type: entry
+                    .r#type
+                    .as_deref()
+                    .and_then(DependencyType::from_section),
+            }
+            .merge_into(&mut packages);
+        }
+    }
+
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outdated_package(
+        name: &str,
+        current: &str,
+        latest: &str,
+        dependents: &[&str],
+    ) -> OutdatedPackage {
+        OutdatedPackage {
+            name: name.to_string(),
+            current: current.to_string(),
+            latest: latest.to_string(),
+            dependents: dependents.iter().map(|d| d.to_string()).collect(),
+            dependency_type: None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = r#"{
+            "foo": {
+                "current": "1.0.0",
+                "wanted": "1.0.1",
+                "latest": "1.1.0",
+                "dependent": "my-app",
+                "location": "/home/me/my-app/node_modules/foo"
+            }
+        }"#;
+
+        let packages = parse(input).unwrap();
+
+        assert_eq!(
+            packages,
+            vec![outdated_package("foo", "1.0.0", "1.1.0", &["my-app"])]
+        );
+    }
+
+    #[test]
+    fn test_parse_long() {
+        let input = r#"{
+            "foo": {
+                "current": "1.0.0",
+                "wanted": "1.0.1",
+                "latest": "1.1.0",
+                "dependent": "my-app",
+                "location": "/home/me/my-app/node_modules/foo",
+                "type": "devDependencies",
+                "homepage": "https://foo.bar"
+            }
+        }"#;
+
+        let packages = parse(input).unwrap();
+
+        assert_eq!(packages[0].dependency_type, Some(DependencyType::Dev));
+    }
+
+    #[test]
+    fn test_parse_workspaces() {
+        let input = r#"{
+            "foo": [
+                {
+                    "current": "1.0.0",
+                    "wanted": "1.0.0",
+                    "latest": "1.2.0",
+                    "dependent": "a",
+                    "location": "/home/me/my-app/node_modules/foo"
+                },
+                {
+                    "current": "1.0.0",
+                    "wanted": "1.0.0",
+                    "latest": "1.2.0",
+                    "dependent": "b",
+                    "location": "/home/me/my-app/node_modules/foo"
+                },
+                {
+                    "current": "1.1.0",
+                    "wanted": "1.1.0",
+                    "latest": "1.2.0",
+                    "dependent": "c",
+                    "location": "/home/me/my-app/packages/c/node_modules/foo"
+                }
+            ],
+            "bar": {
+                "wanted": "2.0.0",
+                "latest": "2.0.0",
+                "dependent": "a",
+                "location": ""
+            }
+        }"#;
+
+        let packages = parse(input).unwrap();
+
+        assert_eq!(
+            packages,
+            vec![
+                outdated_package("foo", "1.0.0", "1.2.0", &["a", "b"]),
+                outdated_package("foo", "1.1.0", "1.2.0", &["c"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use clap::{
+    builder::{PossibleValuesParser, RangedU64ValueParser, TypedValueParser},
+    Args,
+};
+use common::{
+    npm_registry::ReleaseFilter,
+    release::{DependencyType, UpdateKind},
+};
+
+/// Flags that control which releases are fetched for review.
+#[derive(Args, Debug, Clone)]
+pub struct FetchArgs {
+    /// Only review dependencies of these types. Fails for input without types, e.g. `npm outdated --json` without `--long`; otherwise dependencies whose type is unknown are always reviewed
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(["prod", "dev", "optional", "peer"])
+            .map(|dependency_type| dependency_type.parse::<DependencyType>().unwrap()),
+    )]
+    pub only: Vec<DependencyType>,
+
+    /// Kinds of updates to review, compared to the currently installed version
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "minor",
+        value_parser = PossibleValuesParser::new(["patch", "minor", "major"])
+            .map(|kind| kind.parse::<UpdateKind>().unwrap()),
+    )]
+    pub include: Vec<UpdateKind>,
+
+    /// Also include pre-release versions (e.g. `2.0.0-beta.1`) between the current and the latest version
+    #[arg(long)]
+    pub pre_releases: bool,
+
+    /// List every release between the current and the latest version, e.g. `1.3.1` and `1.3.2` next to `1.3.0`, instead of only the first release of each line
+    #[arg(long)]
+    pub intermediate_patches: bool,
+
+    /// How many packages to fetch from the registry at the same time
+    #[arg(
+        long,
+        default_value = "8",
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..),
+    )]
+    pub concurrency: usize,
+}
+
+impl FetchArgs {
+    pub fn release_filter(&self) -> ReleaseFilter {
+        ReleaseFilter {
+            dependency_types: self.only.clone(),
+            kinds: self.include.clone(),
+            include_prereleases: self.pre_releases,
+            include_intermediate_patches: self.intermediate_patches,
+        }
+    }
+}
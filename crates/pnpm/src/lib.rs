@@ -1,28 +1,348 @@
 use anyhow::Result;
-use common::{npm_registry::NpmPackage, release::Release, semver::Semver};
-use futures::future::try_join_all;
+use common::{
+    npm_registry::{Metadata, NpmPackage, ReleaseFilter},
+    npmrc::{self, Npmrc},
+    outdated::OutdatedPackage,
+    release::{DependencyType, PackageError, Release, ReleaseEntry},
+    semver::Semver,
+};
+use futures::{stream, StreamExt};
+use std::collections::BTreeMap;
 
+pub mod fetch_args;
 pub mod pnpm;
 
-pub use crate::pnpm::{parse_input, PnpmOutdatedOutput, PnpmOutdatedPackage};
+pub use crate::fetch_args::FetchArgs;
+pub use crate::pnpm::{parse_input, PnpmOutdatedEntries, PnpmOutdatedOutput, PnpmOutdatedPackage};
 
-pub async fn fetch_all_releases(parsed: PnpmOutdatedOutput) -> Result<Vec<Release>> {
-    let package_futures: Vec<_> = parsed
+/// Fetches the releases of all packages, at most `concurrency` packages at a time. A package that fails doesn't stop the others, but is listed with its error instead of its releases.
+pub async fn fetch_all_releases(
+    packages: Vec<OutdatedPackage>,
+    filter: &ReleaseFilter,
+    concurrency: usize,
+) -> Vec<ReleaseEntry> {
+    fetch_all_releases_with(npmrc::get(), packages, BTreeMap::new(), filter, concurrency).await
+}
+
+/// Like [`fetch_all_releases`], but with the registry configured in `npmrc`, and taking the documents in `npm_packages` instead of fetching them again, e.g. those that [`common::project::outdated_packages`] fetched.
+pub async fn fetch_all_releases_with(
+    npmrc: &Npmrc,
+    packages: Vec<OutdatedPackage>,
+    mut npm_packages: BTreeMap<String, NpmPackage>,
+    filter: &ReleaseFilter,
+    concurrency: usize,
+) -> Vec<ReleaseEntry> {
+    // The same package may be outdated in several workspace projects, with different versions installed. Fetch it only once.
+    let mut packages_by_name: BTreeMap<String, Vec<OutdatedPackage>> = BTreeMap::new();
+    for package in packages {
+        if !filter.includes_dependency_type(package.dependency_type) {
+            continue;
+        }
+        packages_by_name
+            .entry(package.name.clone())
+            .or_default()
+            .push(package);
+    }
+
+    let package_futures = packages_by_name
         .into_iter()
-        .map(async |(package_name, package_info)| {
-            let npm_package = NpmPackage::fetch(&package_name).await?;
-            let current: Semver = package_info.current.parse()?;
-            let latest: Semver = package_info.latest.parse()?;
-            let releases = npm_package.fetch_releases(current, latest).await?;
-            Ok::<Vec<Release>, anyhow::Error>(releases)
+        .map(|(package_name, packages)| {
+            let npm_package = npm_packages.remove(&package_name);
+            (package_name, packages, npm_package)
         })
-        .collect();
+        .map(async |(package_name, packages, npm_package)| {
+            let releases = async {
+                let npm_package = match npm_package {
+                    Some(npm_package) => npm_package,
+                    None => {
+                        NpmPackage::fetch_with(npmrc, &package_name, Metadata::Abbreviated).await?
+                    }
+                };
+                let releases = releases_of(&npm_package, &packages, filter).await?;
+                if !npm_package.lacks_full_metadata(&releases) {
+                    return Ok(releases);
+                }
+
+                // The abbreviated document lacks the repository and `gitHead`, which we need for the release notes
+                let npm_package =
+                    NpmPackage::fetch_with(npmrc, &package_name, Metadata::Full).await?;
+                releases_of(&npm_package, &packages, filter).await
+            };
+
+            releases.await.map_err(|e| PackageError {
+                package: package_name,
+                error: format!("{:#}", e),
+            })
+        });
+
+    let mut all_releases: Vec<Release> = Vec::new();
+    let mut errors: Vec<PackageError> = Vec::new();
+    let results: Vec<_> = stream::iter(package_futures)
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+    for result in results {
+        match result {
+            Ok(releases) => all_releases.extend(releases),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    // Releases newer than several installed versions are listed once, for all of their dependents
+    all_releases.sort();
+    all_releases.dedup_by(|release, kept| {
+        if release.to_string() != kept.to_string() {
+            return false;
+        }
+        kept.dependency_type =
+            DependencyType::most_important(kept.dependency_type, release.dependency_type);
+        kept.dependents.append(&mut release.dependents);
+        kept.dependents.sort();
+        kept.dependents.dedup();
+        true
+    });
 
-    let all_releases: Vec<Release> = try_join_all(package_futures)
-        .await?
+    errors
         .into_iter()
-        .flatten()
-        .collect();
+        .map(ReleaseEntry::Error)
+        .chain(all_releases.into_iter().map(ReleaseEntry::Release))
+        .collect()
+}
+
+/// The releases of `npm_package` that are newer than the installed versions in `packages`.
+async fn releases_of(
+    npm_package: &NpmPackage,
+    packages: &[OutdatedPackage],
+    filter: &ReleaseFilter,
+) -> Result<Vec<Release>> {
+    let mut releases = Vec::new();
+    for package in packages {
+        let current: Semver = package.current.parse()?;
+        let latest: Semver = package.latest.parse()?;
+        for mut release in npm_package.fetch_releases(current, latest, filter).await? {
+            release.dependents = package.dependents.clone();
+            release.dependency_type = package.dependency_type;
+            releases.push(release);
+        }
+    }
+    Ok(releases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{
+        project,
+        test_server::{TestResponse, TestServer},
+    };
+    use std::fs;
+
+    fn packument(name: &str, versions: &[&str]) -> String {
+        let manifests: Vec<String> = versions
+            .iter()
+            .map(|version| {
+                format!(
+                    r#""{}": {{ "name": "{}", "version": "{}", "repository": "owner/{}" }}"#,
+                    version, name, version, name
+                )
+            })
+            .collect();
+        format!(
+            r#"{{ "name": "{}", "dist-tags": {{ "latest": "{}" }}, "versions": {{ {} }} }}"#,
+            name,
+            versions.last().unwrap(),
+            manifests.join(", ")
+        )
+    }
+
+    /// Like [`packument`], but the abbreviated document, without repositories.
+    fn abbreviated_packument(name: &str, versions: &[&str]) -> String {
+        let manifests: Vec<String> = versions
+            .iter()
+            .map(|version| {
+                format!(
+                    r#""{}": {{ "name": "{}", "version": "{}" }}"#,
+                    version, name, version
+                )
+            })
+            .collect();
+        format!(
+            r#"{{ "name": "{}", "dist-tags": {{ "latest": "{}" }}, "versions": {{ {} }} }}"#,
+            name,
+            versions.last().unwrap(),
+            manifests.join(", ")
+        )
+    }
+
+    fn outdated(name: &str, current: &str, latest: &str) -> OutdatedPackage {
+        OutdatedPackage {
+            name: name.to_string(),
+            current: current.to_string(),
+            latest: latest.to_string(),
+            dependents: Vec::new(),
+            dependency_type: None,
+        }
+    }
+
+    #[test]
+    fn test_packages_are_fetched_once() {
+        let server = TestServer::start(|req| match req.target.as_str() {
+            "/foo" => TestResponse::new(200, packument("foo", &["1.0.0", "1.1.0", "1.2.0"])),
+            "/bar" => TestResponse::new(200, packument("bar", &["2.0.0", "2.1.0"])),
+            _ => TestResponse::new(404, "{}"),
+        });
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(&format!("registry={}", server.url("/")), |_| None);
+
+        // Workspace projects may have different versions of the same package installed
+        let packages = vec![
+            outdated("foo", "1.0.0", "1.2.0"),
+            outdated("foo", "1.1.0", "1.2.0"),
+            outdated("bar", "2.0.0", "2.1.0"),
+        ];
+        let entries = smol::block_on(fetch_all_releases_with(
+            &npmrc,
+            packages,
+            BTreeMap::new(),
+            &ReleaseFilter::default(),
+            4,
+        ));
+
+        assert!(entries
+            .iter()
+            .filter_map(ReleaseEntry::as_release)
+            .all(|release| release.repository_url.is_some()));
+        assert_eq!(entries.len(), 3);
+
+        let mut targets: Vec<String> = server
+            .requests()
+            .into_iter()
+            .map(|req| req.target)
+            .collect();
+        targets.sort();
+        assert_eq!(targets, ["/bar", "/foo"]);
+    }
+
+    #[test]
+    fn test_full_documents_are_fetched_for_releases_only() {
+        let server = TestServer::start(|req| {
+            let versions: &[&str] = match req.target.as_str() {
+                "/foo" => &["1.0.0", "1.1.0", "1.2.0"],
+                "/bar" => &["2.0.0", "2.1.0"],
+                _ => return TestResponse::new(404, "{}"),
+            };
+            let name = &req.target[1..];
+            match req.header("accept") {
+                Some(accept) if accept.starts_with("application/vnd.npm.install-v1+json") => {
+                    TestResponse::new(200, abbreviated_packument(name, versions))
+                        .with_header("Content-Type", "application/vnd.npm.install-v1+json")
+                }
+                _ => TestResponse::new(200, packument(name, versions))
+                    .with_header("Content-Type", "application/json"),
+            }
+        });
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(&format!("registry={}", server.url("/")), |_| None);
+
+        // `bar` is outdated in the registry's eyes, but there's nothing newer to review
+        let packages = vec![
+            outdated("foo", "1.0.0", "1.2.0"),
+            outdated("bar", "2.1.0", "2.1.0"),
+        ];
+        let entries = smol::block_on(fetch_all_releases_with(
+            &npmrc,
+            packages,
+            BTreeMap::new(),
+            &ReleaseFilter::default(),
+            4,
+        ));
+
+        let repositories: Vec<Option<&str>> = entries
+            .iter()
+            .filter_map(ReleaseEntry::as_release)
+            .map(|release| release.repository_url.as_deref())
+            .collect();
+        assert_eq!(
+            repositories,
+            [
+                Some("https://github.com/owner/foo"),
+                Some("https://github.com/owner/foo")
+            ]
+        );
+
+        let mut requests: Vec<(String, bool)> = server
+            .requests()
+            .into_iter()
+            .map(|req| {
+                let abbreviated = req.header("accept").is_some_and(|accept| {
+                    accept.starts_with("application/vnd.npm.install-v1+json")
+                });
+                (req.target, abbreviated)
+            })
+            .collect();
+        requests.sort();
+        assert_eq!(
+            requests,
+            [
+                ("/bar".to_string(), true),
+                ("/foo".to_string(), false),
+                ("/foo".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_project_packages_are_fetched_once() {
+        let server = TestServer::start(|req| match req.target.as_str() {
+            "/foo" => TestResponse::new(200, packument("foo", &["1.0.0", "1.1.0"])),
+            "/bar" => TestResponse::new(200, packument("bar", &["2.0.0"])),
+            _ => TestResponse::new(404, "{}"),
+        });
+        let mut npmrc = Npmrc::default();
+        npmrc.parse(&format!("registry={}", server.url("/")), |_| None);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{ "dependencies": { "foo": "^1.0.0", "bar": "^2.0.0" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("package-lock.json"),
+            r#"{ "lockfileVersion": 3, "packages": {
+                "node_modules/foo": { "version": "1.0.0" },
+                "node_modules/bar": { "version": "2.0.0" }
+            } }"#,
+        )
+        .unwrap();
+
+        let entries = smol::block_on(async {
+            let outdated = project::outdated_packages(&npmrc, dir.path(), 4)
+                .await
+                .unwrap();
+            fetch_all_releases_with(
+                &npmrc,
+                outdated.packages,
+                outdated.npm_packages,
+                &ReleaseFilter::default(),
+                4,
+            )
+            .await
+        });
+
+        let releases: Vec<String> = entries
+            .iter()
+            .filter_map(ReleaseEntry::as_release)
+            .map(|release| format!("{}@{}", release.package, release.semver))
+            .collect();
+        assert_eq!(releases, ["foo@1.1.0"]);
 
-    Ok(all_releases)
+        let mut targets: Vec<String> = server
+            .requests()
+            .into_iter()
+            .map(|req| req.target)
+            .collect();
+        targets.sort();
+        assert_eq!(targets, ["/bar", "/foo"]);
+    }
 }
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use common::{outdated::OutdatedPackage, release::DependencyType};
 use serde::Deserialize;
 use std::{
     collections::BTreeMap,
@@ -6,15 +7,143 @@ use std::{
 };
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PnpmOutdatedPackage {
     pub current: String,
     pub latest: String,
+    /// The `package.json` section, e.g. `devDependencies`
+    pub dependency_type: Option<String>,
+    /// Only with `pnpm outdated --recursive`
+    #[serde(default)]
+    pub dependent_packages: Vec<PnpmDependentPackage>,
 }
 
-pub type PnpmOutdatedOutput = BTreeMap<String, PnpmOutdatedPackage>;
+#[derive(Deserialize)]
+pub struct PnpmDependentPackage {
+    pub name: String,
+    pub location: String,
+}
+
+/// With `--recursive`, workspace projects may have different versions of a package installed, which are listed separately.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum PnpmOutdatedEntries {
+    One(PnpmOutdatedPackage),
+    Many(Vec<PnpmOutdatedPackage>),
+}
 
-pub fn parse_input() -> Result<PnpmOutdatedOutput> {
+pub type PnpmOutdatedOutput = BTreeMap<String, PnpmOutdatedEntries>;
+
+pub fn parse_input() -> Result<Vec<OutdatedPackage>> {
     let mut input = String::new();
     io::stdin().read_to_string(&mut input)?;
-    Ok(serde_json::from_str(&input)?)
+    parse(&input)
+}
+
+/// Parses the output of `pnpm outdated --format json`, with or without `--recursive`.
+pub fn parse(input: &str) -> Result<Vec<OutdatedPackage>> {
+    let output: PnpmOutdatedOutput = serde_json::from_str(input)?;
+
+    let mut packages = Vec::new();
+    for (name, entries) in output {
+        let entries = match entries {
+            PnpmOutdatedEntries::One(entry) => vec![entry],
+            PnpmOutdatedEntries::Many(entries) => entries,
+        };
+
+        for entry in entries {
+            OutdatedPackage {
+                name: name.clone(),
+                current: entry.current,
+                latest: entry.latest,
+                dependents: entry
+                    .dependent_packages
+                    .into_iter()
+                    .map(|dependent| dependent.name)
+                    .collect(),
+                dependency_type: entry
+                    .dependency_type
+                    .as_deref()
+                    .and_then(DependencyType::from_section),
+            }
+            .merge_into(&mut packages);
+        }
+    }
+
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = r#"{ "foo": { "current": "1.0.0", "latest": "1.1.0" }}"#;
+
+        let packages = parse(input).unwrap();
+
+        assert_eq!(
+            packages,
+            vec![OutdatedPackage {
+                name: "foo".to_string(),
+                current: "1.0.0".to_string(),
+                latest: "1.1.0".to_string(),
+                dependents: Vec::new(),
+                dependency_type: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_recursive() {
+        let input = r#"{
+            "foo": {
+                "current": "1.0.0",
+                "latest": "1.1.0",
+                "wanted": "1.0.0",
+                "isDeprecated": false,
+                "dependencyType": "dependencies",
+                "dependentPackages": [
+                    { "name": "a", "location": "/repo/packages/a" },
+                    { "name": "b", "location": "/repo/packages/b" }
+                ]
+            },
+            "bar": [
+                {
+                    "current": "2.0.0",
+                    "latest": "2.1.0",
+                    "dependentPackages": [{ "name": "a", "location": "/repo/packages/a" }]
+                },
+                {
+                    "current": "2.0.1",
+                    "latest": "2.1.0",
+                    "dependentPackages": [{ "name": "b", "location": "/repo/packages/b" }]
+                }
+            ]
+        }"#;
+
+        let packages = parse(input).unwrap();
+
+        let summary: Vec<(&str, &str, Vec<&str>, Option<DependencyType>)> = packages
+            .iter()
+            .map(|p| {
+                let dependents = p.dependents.iter().map(|d| d.as_str()).collect();
+                (
+                    p.name.as_str(),
+                    p.current.as_str(),
+                    dependents,
+                    p.dependency_type,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("bar", "2.0.0", vec!["a"], None),
+                ("bar", "2.0.1", vec!["b"], None),
+                ("foo", "1.0.0", vec!["a", "b"], Some(DependencyType::Prod)),
+            ]
+        );
+    }
 }
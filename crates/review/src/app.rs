@@ -2,7 +2,7 @@ use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::{
     layout::Rect,
     style::{Color, Style},
-    text::{Line, Text},
+    text::{Line, Span, Text},
     widgets::{Block, BorderType, Borders, Paragraph, Widget},
 };
 use smol::channel::Sender;
@@ -12,17 +12,26 @@ use crate::multi_select::{MultiSelect, SelectOption};
 use crate::{
     app_shell::AppShell, multi_select::MultiSelectView, release_ext::ReleaseExt, UiMessage,
 };
-use common::release::Release;
+use anyhow::anyhow;
+use common::async_h1_client::cache::NotCached;
+use common::release::{DependencyType, Release, ReleaseEntry, UpdateKind};
 
 const SCROLL_STEP_SIZE: u16 = 5;
 
 pub struct App {
     scroll: u16,
     focused_pane: Pane,
-    multiselect: MultiSelect<Release>,
+    multiselect: MultiSelect<ReleaseEntry>,
     pub should_exit: Option<ExitAction>, // `Ok(…)` if user wants to exit; … == true iff they want to print the selected releases
     left_column_width: u16,
-    release_notes_runner: AsyncTaskRunner<String>,
+    release_notes_runner: AsyncTaskRunner<ReleaseNotes>,
+}
+
+#[derive(Clone)]
+enum ReleaseNotes {
+    Markdown(String),
+    /// Offline, and the release notes weren't fetched before
+    NotCached,
 }
 
 #[derive(PartialEq)]
@@ -38,16 +47,25 @@ pub enum ExitAction {
 }
 
 impl App {
-    pub fn new(releases: &[Release], ui_tx: Sender<UiMessage>) -> App {
+    pub fn new(entries: &[ReleaseEntry], ui_tx: Sender<UiMessage>) -> App {
         let focused_pane = Pane::Releases;
 
-        let options = releases
+        let options = entries
             .iter()
-            .map(|release| {
-                SelectOption::new(
-                    format!("{}@{}", release.package, release.semver),
-                    release.clone(),
-                )
+            .enumerate()
+            .map(|(i, entry)| match entry {
+                ReleaseEntry::Release(release) => {
+                    let previous = i.checked_sub(1).and_then(|j| entries[j].as_release());
+                    SelectOption::new(get_label(release, previous), entry.clone())
+                        // Patches only fix bugs, so we want them unless the user objects
+                        .with_selected(release.kind == UpdateKind::Patch)
+                        .with_badge(release.dependency_type.map(get_badge))
+                }
+                ReleaseEntry::Error(error) => {
+                    SelectOption::new(error.package.clone(), entry.clone())
+                        .with_disabled(true)
+                        .with_badge(Some(Span::styled("error", Style::default().fg(Color::Red))))
+                }
             })
             .collect();
 
@@ -107,10 +125,21 @@ impl App {
     }
 
     pub fn show_release_notes_of_focused_release(&mut self) {
-        let release = self.multiselect.focused_value().clone();
+        let entry = self.multiselect.focused_value().clone();
 
-        self.release_notes_runner
-            .start_operation(async move { ReleaseExt(&release).fetch_release_notes().await });
+        self.release_notes_runner.start_operation(async move {
+            match entry {
+                ReleaseEntry::Release(release) => {
+                    match ReleaseExt(&release).fetch_release_notes().await {
+                        Err(e) if NotCached::is_cause_of(&e) => Ok(ReleaseNotes::NotCached),
+                        result => result.map(ReleaseNotes::Markdown),
+                    }
+                }
+                ReleaseEntry::Error(error) => {
+                    Err(anyhow!("Failed to fetch releases: {}", error.error))
+                }
+            }
+        });
     }
 
     pub fn scroll_up(&mut self) {
@@ -126,7 +155,11 @@ impl App {
     }
 
     pub fn get_selected_releases(&self) -> Vec<&Release> {
-        self.multiselect.selected_values()
+        self.multiselect
+            .selected_values()
+            .into_iter()
+            .filter_map(ReleaseEntry::as_release)
+            .collect()
     }
 
     pub fn shrink_left_column(&mut self) {
@@ -158,6 +191,26 @@ fn owned_markdown_text(markdown: String) -> Text<'static> {
     Text::from(owned_lines)
 }
 
+/// Releases that continue the minor line of the release above them are indented below it, so that e.g. `foo@1.3.0`, `1.3.1` and `1.3.2` read as one group.
+fn get_label(release: &Release, previous: Option<&Release>) -> String {
+    match previous {
+        Some(previous) if release.is_same_minor_line(previous) => {
+            format!("  └ {}", release.semver)
+        }
+        _ => format!("{}@{}", release.package, release.semver),
+    }
+}
+
+/// Production dependencies deserve the most careful review, so they stand out.
+fn get_badge(dependency_type: DependencyType) -> Span<'static> {
+    let color = match dependency_type {
+        DependencyType::Prod => Color::Yellow,
+        _ => Color::DarkGray,
+    };
+
+    Span::styled(dependency_type.to_string(), Style::default().fg(color))
+}
+
 fn get_style(focused: bool) -> Style {
     match focused {
         true => Style::default(),
@@ -168,17 +221,34 @@ fn get_style(focused: bool) -> Style {
 impl Widget for &mut App {
     fn render(self, area: Rect, buf: &mut ratatui::buffer::Buffer) {
         let task_status = self.release_notes_runner.status();
-        let release_notes_text = match task_status {
+        let mut release_notes_text = match task_status {
             AsyncTaskStatus::Idle => Text::styled("--- No release notes ---", Color::Yellow),
             AsyncTaskStatus::Loading => {
                 Text::styled("--- Loading release notes... ---", Color::Gray)
             }
-            AsyncTaskStatus::Loaded(notes) => owned_markdown_text(notes.clone()),
+            AsyncTaskStatus::Loaded(ReleaseNotes::Markdown(notes)) => {
+                owned_markdown_text(notes.clone())
+            }
+            AsyncTaskStatus::Loaded(ReleaseNotes::NotCached) => Text::styled(
+                "--- Release notes not cached; run without --offline to fetch them ---",
+                Color::Yellow,
+            ),
             AsyncTaskStatus::Error(error) => {
                 Text::styled(format!("--- Error: {} ---", error), Color::Red)
             }
         };
 
+        let dependents = match self.multiselect.focused_value() {
+            ReleaseEntry::Release(release) => release.dependents.as_slice(),
+            ReleaseEntry::Error(_) => &[],
+        };
+        if !dependents.is_empty() {
+            let used_by = format!("Used by: {}", dependents.join(", "));
+            release_notes_text
+                .lines
+                .splice(0..0, [Line::styled(used_by, Color::Cyan), Line::default()]);
+        }
+
         let release_notes = Paragraph::new(release_notes_text)
             .wrap(ratatui::widgets::Wrap { trim: true })
             .scroll((self.scroll, 0))
@@ -57,7 +57,7 @@ where
 
             let new_status = match task_result {
                 Ok(value) => AsyncTaskStatus::Loaded(value),
-                Err(e) => AsyncTaskStatus::Error(e.to_string()),
+                Err(e) => AsyncTaskStatus::Error(format!("{:#}", e)),
             };
 
             {
@@ -0,0 +1,196 @@
+//! Finds the notes of a version in changelogs, which come in many flavors:
+//!
+//! - Keep a Changelog: `## [1.2.0] - 2024-01-01`
+//! - conventional-changelog: `# [1.2.0](https://github.com/…/compare/v1.1.0...v1.2.0) (2024-01-01)`, or `##` and `###` for minor and patch releases
+//! - Changesets: `# @scope/name` followed by `## 1.2.0` and `### Minor Changes`
+//! - Older ones with setext headings: `1.2.0 / 2024-01-01` underlined by `===`
+
+/// The file names changelogs commonly go by, most common first.
+pub const FILE_NAMES: [&str; 4] = ["CHANGELOG.md", "changelog.md", "HISTORY.md", "CHANGES.md"];
+
+struct Heading<'a> {
+    /// The index of the heading's first line
+    line: usize,
+    /// The number of lines the heading takes, i.e. 2 for setext headings
+    lines: usize,
+    version: Option<&'a str>,
+}
+
+/// The notes of `version` in `changelog`, i.e. everything between its heading and the next version's heading.
+pub fn version_section(changelog: &str, version: &str) -> Option<String> {
+    let lines: Vec<&str> = changelog.lines().collect();
+    let headings = headings(&lines);
+
+    let index = headings
+        .iter()
+        .position(|heading| heading.version == Some(version))?;
+    let start = headings[index].line + headings[index].lines;
+    let mut end = headings[index + 1..]
+        .iter()
+        .find(|heading| heading.version.is_some())
+        .map_or(lines.len(), |heading| heading.line);
+    // Keep a Changelog ends with the link reference definitions of all versions, e.g. `[1.2.0]: https://…`
+    while end > start && (lines[end - 1].trim().is_empty() || is_link_reference(lines[end - 1])) {
+        end -= 1;
+    }
+
+    let section = lines[start..end].join("\n").trim().to_string();
+    match section.is_empty() {
+        true => None,
+        false => Some(section),
+    }
+}
+
+/// Whether `line` defines the target of reference-style links, e.g. `[1.2.0]: https://…`.
+fn is_link_reference(line: &str) -> bool {
+    line.trim()
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("]:"))
+        .is_some_and(|(label, _)| !label.is_empty() && !label.contains(']'))
+}
+
+/// The ATX (`## …`) and setext headings of the changelog, skipping code blocks.
+fn headings<'a>(lines: &[&'a str]) -> Vec<Heading<'a>> {
+    let mut headings = Vec::new();
+    let mut in_code_block = false;
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        if let Some(text) = atx_heading(trimmed) {
+            headings.push(Heading {
+                line: index,
+                lines: 1,
+                version: heading_version(text),
+            });
+        } else if !trimmed.is_empty() && !trimmed.starts_with(['-', '*', '+', '>', '|']) {
+            let is_underline = |next: &&str| {
+                let next = next.trim();
+                next.len() >= 3
+                    && (next.chars().all(|c| c == '=') || next.chars().all(|c| c == '-'))
+            };
+            if lines.get(index + 1).is_some_and(is_underline) {
+                headings.push(Heading {
+                    line: index,
+                    lines: 2,
+                    version: heading_version(trimmed),
+                });
+            }
+        }
+    }
+
+    headings
+}
+
+/// The text of `line` if it is a heading like `## 1.2.0`.
+fn atx_heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    let level = line.len() - text.len();
+    match (1..=6).contains(&level) && (text.is_empty() || text.starts_with(' ')) {
+        true => Some(text.trim()),
+        false => None,
+    }
+}
+
+/// The first version that `text` mentions, e.g. `1.2.0` for `[v1.2.0](https://…/compare/v1.1.0...v1.2.0) (2024-01-01)`.
+fn heading_version(text: &str) -> Option<&str> {
+    text.split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')))
+        .map(|word| word.strip_prefix(['v', 'V']).unwrap_or(word))
+        .find(|word| is_version(word))
+}
+
+/// Whether `word` looks like `1.2.0`, `1.2` or `1.2.0-beta.1+build`, but not like a date such as `2024-01-01`.
+fn is_version(word: &str) -> bool {
+    let core = word.split(['-', '+']).next().unwrap_or_default();
+    let numbers: Vec<&str> = core.split('.').collect();
+    numbers.len() >= 2
+        && numbers
+            .iter()
+            .all(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keep_a_changelog() {
+        let changelog = "# Changelog\n\nAll notable changes to this project will be documented in this file.\n\n## [Unreleased]\n\n### Added\n- Something new\n\n## [1.2.0] - 2024-01-01\n\n### Fixed\n- A bug\n\n## [1.1.0] - 2023-12-01\n\n### Added\n- A feature\n\n[unreleased]: https://github.com/owner/repo/compare/v1.2.0...HEAD\n[1.2.0]: https://github.com/owner/repo/compare/v1.1.0...v1.2.0\n[1.1.0]: https://github.com/owner/repo/releases/tag/v1.1.0\n";
+
+        assert_eq!(
+            version_section(changelog, "1.2.0").as_deref(),
+            Some("### Fixed\n- A bug")
+        );
+        assert_eq!(
+            version_section(changelog, "1.1.0").as_deref(),
+            Some("### Added\n- A feature")
+        );
+        assert_eq!(version_section(changelog, "1.0.0"), None);
+    }
+
+    #[test]
+    fn test_conventional_changelog() {
+        let changelog = "# Changelog\n\n## [2.1.1](https://github.com/owner/repo/compare/v2.1.0...v2.1.1) (2024-02-02)\n\n### Bug Fixes\n\n* **core:** fix it ([abc1234](https://github.com/owner/repo/commit/abc1234))\n\n# [2.1.0](https://github.com/owner/repo/compare/v2.0.0...v2.1.0) (2024-01-01)\n\n### Features\n\n* add it\n";
+
+        assert_eq!(
+            version_section(changelog, "2.1.1").as_deref(),
+            Some("### Bug Fixes\n\n* **core:** fix it ([abc1234](https://github.com/owner/repo/commit/abc1234))")
+        );
+        assert_eq!(
+            version_section(changelog, "2.1.0").as_deref(),
+            Some("### Features\n\n* add it")
+        );
+        assert_eq!(version_section(changelog, "2.0.0"), None);
+    }
+
+    #[test]
+    fn test_changesets() {
+        let changelog = "# @scope/core\n\n## 5.1.0\n\n### Minor Changes\n\n- 1a2b3c4: Add a thing\n\n### Patch Changes\n\n- Updated dependencies\n  - @scope/utils@2.0.1\n\n## 5.0.0-beta.1\n\n### Major Changes\n\n- Break it\n";
+
+        assert_eq!(
+            version_section(changelog, "5.1.0").as_deref(),
+            Some("### Minor Changes\n\n- 1a2b3c4: Add a thing\n\n### Patch Changes\n\n- Updated dependencies\n  - @scope/utils@2.0.1")
+        );
+        assert_eq!(
+            version_section(changelog, "5.0.0-beta.1").as_deref(),
+            Some("### Major Changes\n\n- Break it")
+        );
+    }
+
+    #[test]
+    fn test_setext_headings() {
+        let changelog = "4.18.2 / 2022-10-08\n===================\n\n  * Fix regression routing a large stack\n\n4.18.1 / 2022-04-29\n===================\n\n  * Fix hanging on large stack\n";
+
+        assert_eq!(
+            version_section(changelog, "4.18.2").as_deref(),
+            Some("* Fix regression routing a large stack")
+        );
+    }
+
+    #[test]
+    fn test_code_blocks_are_not_headings() {
+        let changelog = "## 1.1.0\n\n```sh\n# 1.0.0\n```\n\n## 1.0.0\n\n- Initial release\n";
+
+        assert_eq!(
+            version_section(changelog, "1.1.0").as_deref(),
+            Some("```sh\n# 1.0.0\n```")
+        );
+    }
+
+    #[test]
+    fn test_heading_version() {
+        assert_eq!(heading_version("[1.2.0] - 2024-01-01"), Some("1.2.0"));
+        assert_eq!(heading_version("v1.2.0"), Some("1.2.0"));
+        assert_eq!(heading_version("@scope/name@1.2.0"), Some("1.2.0"));
+        assert_eq!(heading_version("[Unreleased]"), None);
+        assert_eq!(heading_version("2024-01-01"), None);
+        assert_eq!(heading_version("Patch Changes"), None);
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    hosts::{self, Api, Instance},
+    tags::{self, ReleaseHost, TaggedRelease},
+};
+
+const CODEBERG_URL: &str = "https://codeberg.org";
+
+/// The Gitea and Forgejo instances to fetch release notes from: Codeberg with the token in `DEPUTUI_GITEA_TOKEN`, and self-hosted ones from `DEPUTUI_GITEA_HOSTS` with tokens of their own, e.g. `git.corp.example=secret`.
+pub fn instances() -> Vec<Instance> {
+    hosts::instances(CODEBERG_URL, "DEPUTUI_GITEA_TOKEN", "DEPUTUI_GITEA_HOSTS")
+}
+
+#[derive(Debug, Clone)]
+pub struct GiteaRepo {
+    /// The URL of the instance, e.g. `https://codeberg.org`
+    pub base_url: String,
+    pub owner: String,
+    pub repo: String,
+    pub token: Option<String>,
+}
+
+impl GiteaRepo {
+    /// The repository at `url_str` on one of the `instances`, see [`instances`].
+    pub fn from_gitea_url(url_str: &str, instances: &[Instance]) -> Result<GiteaRepo> {
+        let url = Url::parse(url_str)?;
+
+        let Some((instance, segments)) = hosts::split_url(&url, instances)? else {
+            bail!("Not a Gitea URL: {}", url_str);
+        };
+
+        let (Some(owner), Some(repo)) = (segments.first(), segments.get(1)) else {
+            bail!("URL path must contain owner and repo: {}", url_str);
+        };
+        let repo = repo.trim_end_matches(".git");
+        if owner.is_empty() || repo.is_empty() {
+            bail!("URL path must contain owner and repo: {}", url_str);
+        }
+
+        Ok(GiteaRepo {
+            base_url: instance.url,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            token: instance.token,
+        })
+    }
+
+    /// The API URL of `/repos/{owner}/{repo}/` followed by `path`, whose segments are percent-encoded.
+    fn api_url(&self, path: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.base_url)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid Gitea URL: {}", self.base_url))?
+            .pop_if_empty()
+            .extend(["api", "v1", "repos", &self.owner, &self.repo])
+            .extend(path);
+        Ok(url)
+    }
+
+    fn api(&self) -> Api<'static> {
+        Api {
+            name: "Gitea",
+            json: "application/json",
+            auth: self
+                .token
+                .clone()
+                .map(|token| ("Authorization", format!("token {}", token))),
+        }
+    }
+}
+
+impl ReleaseHost for GiteaRepo {
+    type Release = GiteaRelease;
+
+    async fn fetch_release(&self, tag: &str) -> Result<Option<GiteaRelease>> {
+        let url = self.api_url(&["releases", "tags", tag])?;
+        self.api().fetch_json(url).await
+    }
+
+    async fn fetch_releases(&self) -> Result<Vec<GiteaRelease>> {
+        let mut url = self.api_url(&["releases"])?;
+        // Instances cap this at their maximum page size, 50 by default
+        url.query_pairs_mut()
+            .append_pair("limit", &tags::RELEASES_PER_PAGE.to_string());
+        Ok(self.api().fetch_json(url).await?.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GiteaRelease {
+    #[serde(default)]
+    pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub body: Option<String>,
+}
+
+impl TaggedRelease for GiteaRelease {
+    fn tag(&self) -> &str {
+        &self.tag_name
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::test_server::{TestRequest, TestResponse, TestServer};
+
+    #[test]
+    fn test_parse_repositories_under_sub_paths() {
+        let instances = [
+            CODEBERG_URL,
+            "https://git.corp.example",
+            "https://corp.example/gitea/",
+        ]
+        .map(|url| Instance {
+            url: url.to_string(),
+            token: None,
+        });
+
+        for (url, base_url, owner, repo) in [
+            (
+                "https://codeberg.org/owner/repo",
+                "https://codeberg.org",
+                "owner",
+                "repo",
+            ),
+            (
+                "https://codeberg.org/owner/repo.git",
+                "https://codeberg.org",
+                "owner",
+                "repo",
+            ),
+            (
+                "https://git.corp.example/team/repo/src/branch/main",
+                "https://git.corp.example",
+                "team",
+                "repo",
+            ),
+            (
+                "https://corp.example/gitea/team/repo",
+                "https://corp.example/gitea",
+                "team",
+                "repo",
+            ),
+        ] {
+            let gitea_repo = GiteaRepo::from_gitea_url(url, &instances).unwrap();
+            assert_eq!(gitea_repo.base_url, base_url, "{}", url);
+            assert_eq!(gitea_repo.owner, owner, "{}", url);
+            assert_eq!(gitea_repo.repo, repo, "{}", url);
+        }
+
+        for url in [
+            "https://github.com/owner/repo",
+            "https://codeberg.org/owner",
+            "https://codeberg.org//repo",
+            "https://forgejo.example/owner/repo",
+            "https://corp.example/team/repo",
+            "not-a-url",
+        ] {
+            assert!(
+                GiteaRepo::from_gitea_url(url, &instances).is_err(),
+                "Should fail for URL: {}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_instances_under_sub_paths() {
+        let server = TestServer::start(|req: &TestRequest| match req.target.as_str() {
+            "/gitea/api/v1/repos/owner/repo/releases/tags/1.2.0" => TestResponse::new(
+                200,
+                r#"{ "tag_name": "1.2.0", "name": "1.2.0", "body": "Notes" }"#,
+            ),
+            "/gitea/api/v1/repos/owner/repo/releases?limit=100" => TestResponse::new(
+                200,
+                r#"[{ "tag_name": "1.3.0", "name": "1.3.0", "body": "Latest" }]"#,
+            ),
+            _ => TestResponse::new(404, r#"{"message":"not found"}"#),
+        });
+        let instances = [Instance {
+            url: server.url("/gitea"),
+            token: Some("secret".to_string()),
+        }];
+        let gitea_repo =
+            GiteaRepo::from_gitea_url(&server.url("/gitea/owner/repo"), &instances).unwrap();
+
+        let release = smol::block_on(gitea_repo.fetch_release_by_version("foo", "1.2.0")).unwrap();
+        assert_eq!(release.body.as_deref(), Some("Notes"));
+
+        for req in server.requests() {
+            assert!(
+                req.target.starts_with("/gitea/api/v1/repos/owner/repo/"),
+                "{}",
+                req.target
+            );
+            assert_eq!(req.header("authorization"), Some("token secret"));
+        }
+    }
+
+    #[test]
+    fn test_missing_tags_fall_through_to_the_next_candidate() {
+        let server = TestServer::start(|req: &TestRequest| match req.target.as_str() {
+            "/api/v1/repos/owner/repo/releases/tags/v1.2.0" => TestResponse::new(
+                200,
+                r#"{ "tag_name": "v1.2.0", "name": "v1.2.0", "body": "Notes" }"#,
+            ),
+            // Only the latest release is listed
+            "/api/v1/repos/owner/repo/releases?limit=100" => TestResponse::new(
+                200,
+                r#"[{ "tag_name": "v1.3.0", "name": "v1.3.0", "body": "Latest" }]"#,
+            ),
+            _ => TestResponse::new(404, r#"{"message":"not found"}"#),
+        });
+        let instances = [Instance {
+            url: server.url(""),
+            token: None,
+        }];
+        let gitea_repo = GiteaRepo::from_gitea_url(&server.url("/owner/repo"), &instances).unwrap();
+
+        let release = smol::block_on(gitea_repo.fetch_release_by_version("foo", "1.2.0")).unwrap();
+        assert_eq!(release.body.as_deref(), Some("Notes"));
+
+        let targets: Vec<String> = server
+            .requests()
+            .into_iter()
+            .map(|req| req.target)
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                "/api/v1/repos/owner/repo/releases?limit=100",
+                "/api/v1/repos/owner/repo/releases/tags/1.2.0",
+                "/api/v1/repos/owner/repo/releases/tags/v1.2.0",
+            ]
+        );
+    }
+}
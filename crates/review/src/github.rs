@@ -1,16 +1,27 @@
 use anyhow::{anyhow, bail, Result};
-use http_types::{Method, Request};
 use serde::{Deserialize, Serialize};
-use smol::io::AsyncReadExt;
 use std::env;
 use url::Url;
 
-use common::async_h1_client;
+use crate::{
+    changelog,
+    hosts::Api,
+    tags::{self, ReleaseHost, TaggedRelease},
+};
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Serves files of repositories without counting against the API's rate limit.
+const GITHUB_RAW_URL: &str = "https://raw.githubusercontent.com";
 
 #[derive(Debug, Clone)]
 pub struct GitHubRepo {
     pub owner: String,
     pub repo: String,
+    /// E.g. `https://api.github.com`, or a fake one in tests
+    pub api_url: String,
+    /// E.g. `https://raw.githubusercontent.com`
+    pub raw_url: String,
 }
 
 impl GitHubRepo {
@@ -41,59 +52,252 @@ impl GitHubRepo {
             bail!("Empty repo name in URL: {}", url_str);
         }
 
-        Ok(GitHubRepo { owner, repo })
+        Ok(GitHubRepo {
+            owner,
+            repo,
+            api_url: GITHUB_API_URL.to_string(),
+            raw_url: GITHUB_RAW_URL.to_string(),
+        })
     }
 
-    pub async fn fetch_release(&self, tag: &str) -> Result<GitHubRelease> {
-        let url = format!(
-            "https://api.github.com/repos/{}/{}/releases/tags/{}",
-            self.owner, self.repo, tag
-        );
-
-        let mut req = Request::new(Method::Get, Url::parse(&url)?);
-        req.insert_header("User-Agent", "deputui-review/0.1.0");
-        req.insert_header("Accept", "application/vnd.github.v3+json");
-
-        if let Ok(token) = env::var("DEPUTUI_GITHUB_TOKEN") {
-            req.insert_header("Authorization", format!("Bearer {}", token));
-        }
-
-        let mut resp = async_h1_client::fetch(req).await?;
+    /// The API URL of `/repos/{owner}/{repo}/` followed by `path`, whose segments are percent-encoded, e.g. the slash of `@scope/name@1.0.0`.
+    fn api_url(&self, path: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.api_url)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid GitHub API URL: {}", self.api_url))?
+            .pop_if_empty()
+            .extend(["repos", &self.owner, &self.repo])
+            .extend(path);
+        Ok(url)
+    }
 
-        if resp.status() != 200 {
-            bail!("GitHub API error: {}", resp.status());
+    fn api(&self) -> Api<'static> {
+        Api {
+            name: "GitHub",
+            json: "application/vnd.github.v3+json",
+            auth: env::var("DEPUTUI_GITHUB_TOKEN")
+                .ok()
+                .map(|token| ("Authorization", format!("Bearer {}", token))),
         }
+    }
 
-        let mut body = Vec::new();
-        resp.read_to_end(&mut body).await?;
-        let response_text = String::from_utf8_lossy(&body);
+    /// The path and content of the changelog at `git_ref`, e.g. the commit a version was published from. In monorepos, the package's own changelog in `directory` is preferred over the repository's.
+    pub async fn fetch_changelog(
+        &self,
+        directory: Option<&str>,
+        git_ref: Option<&str>,
+    ) -> Result<Option<(String, String)>> {
+        let directories = directory
+            .map(|directory| directory.trim_matches('/'))
+            .filter(|directory| !directory.is_empty())
+            .into_iter()
+            .chain([""]);
 
-        serde_json::from_str(&response_text)
-            .map_err(|e| anyhow!("Failed to parse GitHub API response: {}", e))
-    }
+        for directory in directories {
+            for file_name in changelog::FILE_NAMES {
+                let path = match directory {
+                    "" => file_name.to_string(),
+                    directory => format!("{}/{}", directory, file_name),
+                };
 
-    pub async fn fetch_release_by_version(&self, version: &str) -> Result<GitHubRelease> {
-        let tags_to_try = vec![version.to_string(), format!("v{}", version)];
+                let mut url = Url::parse(&self.raw_url)?;
+                url.path_segments_mut()
+                    .map_err(|_| anyhow!("Invalid GitHub URL: {}", self.raw_url))?
+                    .pop_if_empty()
+                    .extend([&self.owner, &self.repo, git_ref.unwrap_or("HEAD")])
+                    .extend(path.split('/'));
 
-        for tag in tags_to_try {
-            match self.fetch_release(&tag).await {
-                Ok(release) => return Ok(release),
-                Err(_) => continue, // Try next tag format
+                if let Some(body) = self.api().fetch(url, "text/plain").await? {
+                    return Ok(Some((path, String::from_utf8_lossy(&body).into_owned())));
+                }
             }
         }
 
-        bail!("No release found")
+        Ok(None)
     }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GitHubRelease {
+    #[serde(default)]
+    pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
     pub body: Option<String>,
 }
 
+impl ReleaseHost for GitHubRepo {
+    type Release = GitHubRelease;
+
+    async fn fetch_release(&self, tag: &str) -> Result<Option<GitHubRelease>> {
+        let url = self.api_url(&["releases", "tags", tag])?;
+        self.api().fetch_json(url).await
+    }
+
+    async fn fetch_releases(&self) -> Result<Vec<GitHubRelease>> {
+        let mut url = self.api_url(&["releases"])?;
+        url.query_pairs_mut()
+            .append_pair("per_page", &tags::RELEASES_PER_PAGE.to_string());
+        Ok(self.api().fetch_json(url).await?.unwrap_or_default())
+    }
+}
+
+impl TaggedRelease for GitHubRelease {
+    fn tag(&self) -> &str {
+        &self.tag_name
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use common::test_server::{TestRequest, TestResponse, TestServer};
+
+    /// A fake GitHub API serving the releases of `owner/repo` with the given tags and names.
+    fn fake_github(releases: &'static [(&'static str, &'static str)]) -> (TestServer, GitHubRepo) {
+        fake_github_listing(releases, releases.len())
+    }
+
+    /// Like [`fake_github`], but listing only the first `listed` releases, as if the others were older than a page.
+    fn fake_github_listing(
+        releases: &'static [(&'static str, &'static str)],
+        listed: usize,
+    ) -> (TestServer, GitHubRepo) {
+        let release_json = |(tag, name): &(&str, &str)| serde_json::json!({ "tag_name": tag, "name": name, "body": format!("Notes of {}", tag) });
+
+        let server = TestServer::start(move |req: &TestRequest| {
+            let path = req.target.split('?').next().unwrap();
+            if path == "/repos/owner/repo/releases" {
+                let list: Vec<_> = releases.iter().take(listed).map(release_json).collect();
+                return TestResponse::new(200, serde_json::to_vec(&list).unwrap());
+            }
+
+            let tag = path
+                .strip_prefix("/repos/owner/repo/releases/tags/")
+                .unwrap_or_default();
+            let tag = tag.replace("%2F", "/");
+            match releases.iter().find(|(t, _)| *t == tag) {
+                Some(release) => {
+                    TestResponse::new(200, serde_json::to_vec(&release_json(release)).unwrap())
+                }
+                None => TestResponse::new(404, r#"{"message":"Not Found"}"#),
+            }
+        });
+
+        let repo = GitHubRepo {
+            owner: "owner".to_string(),
+            repo: "repo".to_string(),
+            api_url: server.url(""),
+            raw_url: server.url(""),
+        };
+        (server, repo)
+    }
+
+    fn release_notes(repo: &GitHubRepo, package: &str, version: &str) -> Option<String> {
+        smol::block_on(repo.fetch_release_by_version(package, version))
+            .ok()
+            .and_then(|release| release.body)
+    }
+
+    #[test]
+    fn test_plain_tags() {
+        let (_server, repo) = fake_github(&[("v1.0.0", "v1.0.0"), ("2.0.0", "2.0.0")]);
+
+        assert_eq!(
+            release_notes(&repo, "foo", "1.0.0").as_deref(),
+            Some("Notes of v1.0.0")
+        );
+        assert_eq!(
+            release_notes(&repo, "foo", "2.0.0").as_deref(),
+            Some("Notes of 2.0.0")
+        );
+    }
+
+    #[test]
+    fn test_monorepo_tags() {
+        let (server, repo) = fake_github(&[
+            ("@babel/core@7.24.0", "v7.24.0"),
+            ("@tanstack/query-core@5.1.0", "@tanstack/query-core@5.1.0"),
+            ("core-v1.2.0", "core: v1.2.0"),
+        ]);
+
+        assert_eq!(
+            release_notes(&repo, "@babel/core", "7.24.0").as_deref(),
+            Some("Notes of @babel/core@7.24.0")
+        );
+        assert_eq!(
+            release_notes(&repo, "@tanstack/query-core", "5.1.0").as_deref(),
+            Some("Notes of @tanstack/query-core@5.1.0")
+        );
+        assert_eq!(
+            release_notes(&repo, "@acme/core", "1.2.0").as_deref(),
+            Some("Notes of core-v1.2.0")
+        );
+
+        // All of them are listed
+        assert!(server
+            .requests()
+            .iter()
+            .all(|req| req.target == "/repos/owner/repo/releases?per_page=100"));
+    }
+
+    #[test]
+    fn test_unlisted_releases_are_fetched_by_tag() {
+        let (server, repo) = fake_github_listing(
+            &[("v8.0.0", "v8.0.0"), ("@babel/core@7.24.0", "v7.24.0")],
+            1,
+        );
+
+        assert_eq!(
+            release_notes(&repo, "@babel/core", "7.24.0").as_deref(),
+            Some("Notes of @babel/core@7.24.0")
+        );
+
+        // The slash of scoped packages must not split the tag into path segments
+        assert!(server
+            .requests()
+            .iter()
+            .any(|req| req.target == "/repos/owner/repo/releases/tags/@babel%2Fcore@7.24.0"));
+    }
+
+    #[test]
+    fn test_falls_back_to_listing_releases() {
+        let (_server, repo) = fake_github(&[
+            ("release-2024-01", "Release 3.0.0"),
+            ("ui-3.0.0-rc", "ui 3.0.0"),
+            ("api-3.0.0-rc", "api 3.0.0"),
+        ]);
+
+        // Releases naming the package win over others of the same version
+        assert_eq!(
+            release_notes(&repo, "@acme/api", "3.0.0").as_deref(),
+            Some("Notes of api-3.0.0-rc")
+        );
+        assert_eq!(
+            release_notes(&repo, "other", "3.0.0").as_deref(),
+            Some("Notes of release-2024-01")
+        );
+        assert_eq!(release_notes(&repo, "other", "4.0.0"), None);
+    }
+
+    #[test]
+    fn test_other_errors_are_not_swallowed() {
+        let server = TestServer::start(|_| TestResponse::new(403, "rate limited"));
+        let repo = GitHubRepo {
+            owner: "owner".to_string(),
+            repo: "repo".to_string(),
+            api_url: server.url(""),
+            raw_url: server.url(""),
+        };
+
+        let error = smol::block_on(repo.fetch_release_by_version("foo", "1.0.0")).unwrap_err();
+        assert_eq!(error.to_string(), "GitHub API error: 403");
+        assert_eq!(server.requests().len(), 1);
+    }
 
     #[test]
     fn test_parse_valid_github_urls() {
@@ -122,4 +326,36 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_fetch_changelog() {
+        let server = TestServer::start(|req| match req.target.as_str() {
+            "/owner/repo/abc123/packages/core/CHANGELOG.md" => TestResponse::new(200, "# core"),
+            "/owner/repo/HEAD/HISTORY.md" => TestResponse::new(200, "# history"),
+            _ => TestResponse::new(404, "404: Not Found"),
+        });
+        let repo = GitHubRepo {
+            owner: "owner".to_string(),
+            repo: "repo".to_string(),
+            api_url: server.url(""),
+            raw_url: server.url(""),
+        };
+
+        let changelog =
+            |directory, git_ref| smol::block_on(repo.fetch_changelog(directory, git_ref)).unwrap();
+
+        assert_eq!(
+            changelog(Some("packages/core"), Some("abc123")),
+            Some((
+                "packages/core/CHANGELOG.md".to_string(),
+                "# core".to_string()
+            ))
+        );
+        // Falls back to the repository's changelog
+        assert_eq!(
+            changelog(Some("packages/other/"), None),
+            Some(("HISTORY.md".to_string(), "# history".to_string()))
+        );
+        assert_eq!(changelog(None, Some("abc123")), None);
+    }
 }
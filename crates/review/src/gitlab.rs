@@ -0,0 +1,241 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    hosts::{self, Api, Instance},
+    tags::{self, ReleaseHost, TaggedRelease},
+};
+
+const GITLAB_URL: &str = "https://gitlab.com";
+
+/// The GitLab instances to fetch release notes from: gitlab.com with the token in `DEPUTUI_GITLAB_TOKEN`, and self-hosted ones from `DEPUTUI_GITLAB_HOSTS` with tokens of their own, e.g. `gitlab.corp.example=secret,https://corp.example/gitlab`.
+pub fn instances() -> Vec<Instance> {
+    hosts::instances(GITLAB_URL, "DEPUTUI_GITLAB_TOKEN", "DEPUTUI_GITLAB_HOSTS")
+}
+
+#[derive(Debug, Clone)]
+pub struct GitLabProject {
+    /// The URL of the instance, e.g. `https://gitlab.com`, or `https://corp.example/gitlab` if it's served under a path
+    pub base_url: String,
+    /// E.g. `group/subgroup/project`
+    pub path: String,
+    /// The token of the instance
+    pub token: Option<String>,
+}
+
+impl GitLabProject {
+    /// The project at `url_str` on one of the `instances`, see [`instances`].
+    pub fn from_gitlab_url(url_str: &str, instances: &[Instance]) -> Result<GitLabProject> {
+        let url = Url::parse(url_str)?;
+
+        let Some((instance, segments)) = hosts::split_url(&url, instances)? else {
+            bail!("Not a GitLab URL: {}", url_str);
+        };
+
+        // Pages of a project follow a `-` segment, e.g. `group/project/-/tree/main`
+        let mut project_segments: Vec<&str> = segments
+            .into_iter()
+            .take_while(|segment| *segment != "-")
+            .collect();
+        if let Some(last) = project_segments.last_mut() {
+            *last = last.trim_end_matches(".git");
+        }
+        if project_segments.len() < 2 || project_segments.iter().any(|s| s.is_empty()) {
+            bail!("URL path must contain namespace and project: {}", url_str);
+        }
+
+        Ok(GitLabProject {
+            base_url: instance.url,
+            path: project_segments.join("/"),
+            token: instance.token,
+        })
+    }
+
+    /// The API URL of `/projects/:id/` followed by `path`. The project's path serves as its ID, with its slashes encoded.
+    fn api_url(&self, path: &[&str]) -> Result<Url> {
+        let mut url = Url::parse(&self.base_url)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid GitLab URL: {}", self.base_url))?
+            .pop_if_empty()
+            .extend(["api", "v4", "projects", &self.path])
+            .extend(path);
+        Ok(url)
+    }
+
+    fn api(&self) -> Api<'static> {
+        Api {
+            name: "GitLab",
+            json: "application/json",
+            auth: self.token.clone().map(|token| ("PRIVATE-TOKEN", token)),
+        }
+    }
+}
+
+impl ReleaseHost for GitLabProject {
+    type Release = GitLabRelease;
+
+    async fn fetch_release(&self, tag: &str) -> Result<Option<GitLabRelease>> {
+        let url = self.api_url(&["releases", tag])?;
+        self.api().fetch_json(url).await
+    }
+
+    async fn fetch_releases(&self) -> Result<Vec<GitLabRelease>> {
+        let mut url = self.api_url(&["releases"])?;
+        url.query_pairs_mut()
+            .append_pair("per_page", &tags::RELEASES_PER_PAGE.to_string());
+        Ok(self.api().fetch_json(url).await?.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitLabRelease {
+    #[serde(default)]
+    pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The release notes, in Markdown
+    pub description: Option<String>,
+}
+
+impl TaggedRelease for GitLabRelease {
+    fn tag(&self) -> &str {
+        &self.tag_name
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::test_server::{TestRequest, TestResponse, TestServer};
+
+    #[test]
+    fn test_parse_project_paths() {
+        let instances = [GITLAB_URL, "https://corp.example/gitlab/"].map(|url| Instance {
+            url: url.to_string(),
+            token: None,
+        });
+
+        for (url, base_url, path) in [
+            (
+                "https://gitlab.com/owner/repo",
+                "https://gitlab.com",
+                "owner/repo",
+            ),
+            (
+                "https://gitlab.com/group/subgroup/repo.git",
+                "https://gitlab.com",
+                "group/subgroup/repo",
+            ),
+            (
+                "https://gitlab.com/group/repo/-/tree/main/packages/foo",
+                "https://gitlab.com",
+                "group/repo",
+            ),
+            (
+                "https://corp.example/gitlab/team/repo",
+                "https://corp.example/gitlab",
+                "team/repo",
+            ),
+        ] {
+            let project = GitLabProject::from_gitlab_url(url, &instances).unwrap();
+            assert_eq!(project.base_url, base_url, "{}", url);
+            assert_eq!(project.path, path, "{}", url);
+        }
+
+        for url in [
+            "https://github.com/owner/repo",
+            "https://gitlab.com/owner",
+            "https://corp.example/team/repo",
+            "https://gitlab.corp.example/team/repo",
+            "not-a-url",
+        ] {
+            assert!(
+                GitLabProject::from_gitlab_url(url, &instances).is_err(),
+                "Should fail for URL: {}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_group_paths_are_encoded() {
+        let server = TestServer::start(|req: &TestRequest| match req.target.as_str() {
+            "/api/v4/projects/group%2Fsub%2Frepo/releases/@scope%2Fcore@1.2.0" => {
+                TestResponse::new(
+                    200,
+                    r#"{ "tag_name": "@scope/core@1.2.0", "name": "1.2.0", "description": "Notes" }"#,
+                )
+            }
+            "/api/v4/projects/group%2Fsub%2Frepo/releases?per_page=100" => TestResponse::new(
+                200,
+                r#"[{ "tag_name": "@scope/core@1.3.0", "name": "1.3.0", "description": "Latest" }]"#,
+            ),
+            _ => TestResponse::new(404, r#"{"message":"404 Not Found"}"#),
+        });
+        let instances = [Instance {
+            url: server.url(""),
+            token: Some("secret".to_string()),
+        }];
+        let project =
+            GitLabProject::from_gitlab_url(&server.url("/group/sub/repo/-/releases"), &instances)
+                .unwrap();
+
+        let release =
+            smol::block_on(project.fetch_release_by_version("@scope/core", "1.2.0")).unwrap();
+        assert_eq!(release.description.as_deref(), Some("Notes"));
+
+        for req in server.requests() {
+            assert!(
+                req.target
+                    .starts_with("/api/v4/projects/group%2Fsub%2Frepo/releases"),
+                "{}",
+                req.target
+            );
+            assert_eq!(req.header("private-token"), Some("secret"));
+        }
+    }
+
+    #[test]
+    fn test_missing_tags_fall_through_to_the_next_candidate() {
+        let server = TestServer::start(|req: &TestRequest| match req.target.as_str() {
+            "/api/v4/projects/owner%2Frepo/releases/v1.2.0" => TestResponse::new(
+                200,
+                r#"{ "tag_name": "v1.2.0", "name": "v1.2.0", "description": "Notes" }"#,
+            ),
+            // Only the latest release is listed
+            "/api/v4/projects/owner%2Frepo/releases?per_page=100" => TestResponse::new(
+                200,
+                r#"[{ "tag_name": "v1.3.0", "name": "v1.3.0", "description": "Latest" }]"#,
+            ),
+            _ => TestResponse::new(404, r#"{"message":"404 Not Found"}"#),
+        });
+        let instances = [Instance {
+            url: server.url(""),
+            token: None,
+        }];
+        let project =
+            GitLabProject::from_gitlab_url(&server.url("/owner/repo"), &instances).unwrap();
+
+        let release = smol::block_on(project.fetch_release_by_version("foo", "1.2.0")).unwrap();
+        assert_eq!(release.description.as_deref(), Some("Notes"));
+
+        let targets: Vec<String> = server
+            .requests()
+            .into_iter()
+            .map(|req| req.target)
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                "/api/v4/projects/owner%2Frepo/releases?per_page=100",
+                "/api/v4/projects/owner%2Frepo/releases/1.2.0",
+                "/api/v4/projects/owner%2Frepo/releases/v1.2.0",
+            ]
+        );
+    }
+}
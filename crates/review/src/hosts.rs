@@ -0,0 +1,200 @@
+//! Forges like GitHub, GitLab and Gitea: their instances and APIs. Self-hosted instances cannot be told apart by their URLs, so users list them in environment variables.
+
+use anyhow::{anyhow, bail, Result};
+use http_types::{Method, Request};
+use serde::de::DeserializeOwned;
+use smol::io::AsyncReadExt;
+use std::env;
+use url::Url;
+
+use common::async_h1_client;
+
+/// An instance of a forge, and the token to authenticate with, which is sent to it only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instance {
+    /// E.g. `https://gitlab.com`, or `https://corp.example/gitlab` if it's served under a path
+    pub url: String,
+    pub token: Option<String>,
+}
+
+/// `default`, e.g. `https://gitlab.com`, with the token in the variable `token_var`, followed by the instances listed in the variable `hosts_var`.
+pub fn instances(default: &str, token_var: &str, hosts_var: &str) -> Vec<Instance> {
+    parse_instances(
+        default,
+        env::var(token_var).ok(),
+        &env::var(hosts_var).unwrap_or_default(),
+    )
+}
+
+/// `hosts` is a comma-separated list of instances, each with an optional token, e.g. `gitlab.corp.example=secret,https://corp.example/gitlab`.
+fn parse_instances(default: &str, default_token: Option<String>, hosts: &str) -> Vec<Instance> {
+    let configured = hosts
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .map(|host| {
+            let (host, token) = match host.split_once('=') {
+                Some((host, token)) => (host.trim(), Some(token.trim().to_string())),
+                None => (host, None),
+            };
+            let url = match host.contains("://") {
+                true => host.to_string(),
+                false => format!("https://{}", host),
+            };
+            Instance { url, token }
+        });
+
+    [Instance {
+        url: default.to_string(),
+        token: default_token,
+    }]
+    .into_iter()
+    .chain(configured)
+    .collect()
+}
+
+/// The API of a forge, and how to authenticate with it.
+pub struct Api<'a> {
+    /// E.g. `GitLab`, for errors
+    pub name: &'a str,
+    /// The media type of JSON responses, e.g. `application/vnd.github.v3+json`
+    pub json: &'a str,
+    /// The header authenticating requests, e.g. `PRIVATE-TOKEN` and the token
+    pub auth: Option<(&'a str, String)>,
+}
+
+impl Api<'_> {
+    /// GETs `url` from the API, or `None` if it's not found.
+    pub async fn fetch_json<T: DeserializeOwned>(&self, url: Url) -> Result<Option<T>> {
+        let Some(body) = self.fetch(url, self.json).await? else {
+            return Ok(None);
+        };
+        let response_text = String::from_utf8_lossy(&body);
+
+        serde_json::from_str(&response_text)
+            .map(Some)
+            .map_err(|e| anyhow!("Failed to parse {} API response: {}", self.name, e))
+    }
+
+    /// GETs `url`, or `None` if it's not found.
+    pub async fn fetch(&self, url: Url, accept: &str) -> Result<Option<Vec<u8>>> {
+        let mut req = Request::new(Method::Get, url);
+        req.insert_header("User-Agent", "deputui-review/0.1.0");
+        req.insert_header("Accept", accept);
+
+        if let Some((name, value)) = &self.auth {
+            req.insert_header(*name, value.as_str());
+        }
+
+        let mut resp = async_h1_client::fetch_cached(req).await?;
+
+        if resp.status() == 404 {
+            return Ok(None);
+        }
+        if resp.status() != 200 {
+            bail!("{} API error: {}", self.name, resp.status());
+        }
+
+        let mut body = Vec::new();
+        resp.read_to_end(&mut body).await?;
+        Ok(Some(body))
+    }
+}
+
+/// The instance among `instances` that `url` belongs to, with a URL without trailing slash, and the path segments of `url` below it, e.g. `https://corp.example/gitlab` and `["team", "repo"]` for `https://corp.example/gitlab/team/repo`.
+pub fn split_url<'a>(
+    url: &'a Url,
+    instances: &[Instance],
+) -> Result<Option<(Instance, Vec<&'a str>)>> {
+    for instance in instances {
+        let base_url = Url::parse(&instance.url)
+            .map_err(|e| anyhow!("Invalid instance URL {}: {}", instance.url, e))?;
+        if base_url.host_str() != url.host_str() || base_url.port() != url.port() {
+            continue;
+        }
+
+        let base_segments: Vec<&str> = base_url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let segments: Vec<&str> = url.path_segments().into_iter().flatten().collect();
+        if segments.starts_with(&base_segments) {
+            let rest = segments[base_segments.len()..].to_vec();
+            let instance = Instance {
+                url: instance.url.trim_end_matches('/').to_string(),
+                token: instance.token.clone(),
+            };
+            return Ok(Some((instance, rest)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::test_server::{TestResponse, TestServer};
+
+    #[test]
+    fn test_parse_instances() {
+        let instances = parse_instances(
+            "https://gitlab.com",
+            Some("public".to_string()),
+            "gitlab.corp.example=corp, https://corp.example/gitlab ,",
+        );
+
+        assert_eq!(
+            instances,
+            [
+                Instance {
+                    url: "https://gitlab.com".to_string(),
+                    token: Some("public".to_string()),
+                },
+                Instance {
+                    url: "https://gitlab.corp.example".to_string(),
+                    token: Some("corp".to_string()),
+                },
+                Instance {
+                    url: "https://corp.example/gitlab".to_string(),
+                    token: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokens_are_sent_to_their_instance_only() {
+        let server = TestServer::start(|_| TestResponse::new(404, "{}"));
+        let instances = [
+            Instance {
+                url: server.url("/corp"),
+                token: Some("secret".to_string()),
+            },
+            Instance {
+                url: server.url("/other"),
+                token: None,
+            },
+        ];
+
+        for path in ["/corp/owner/repo", "/other/owner/repo"] {
+            let url = Url::parse(&server.url(path)).unwrap();
+            let (instance, _) = split_url(&url, &instances).unwrap().unwrap();
+            let api = Api {
+                name: "Forge",
+                json: "application/json",
+                auth: instance.token.map(|token| ("PRIVATE-TOKEN", token)),
+            };
+            assert_eq!(smol::block_on(api.fetch_json::<()>(url)).unwrap(), None);
+        }
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        for req in requests {
+            let expected = req.target.starts_with("/corp/").then_some("secret");
+            assert_eq!(req.header("private-token"), expected, "{}", req.target);
+        }
+    }
+}
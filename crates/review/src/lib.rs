@@ -8,12 +8,21 @@ use std::io::{self, Read};
 mod app;
 mod app_shell;
 mod async_task;
+mod changelog;
+mod gitea;
 mod github;
+mod gitlab;
+mod hosts;
 mod multi_select;
+pub mod output_args;
 mod release_ext;
+mod tags;
+mod tarball;
 mod tui;
 
-use common::release::Release;
+pub use crate::output_args::OutputArgs;
+
+use common::release::{Release, ReleaseEntry};
 
 #[derive(Debug)]
 pub enum UiMessage {
@@ -21,9 +30,9 @@ pub enum UiMessage {
     TaskComplete,
 }
 
-pub async fn run_review_tui(releases: Vec<Release>) -> Result<Vec<String>> {
-    let mut sorted_releases = releases;
-    sorted_releases.sort();
+pub async fn run_review_tui(entries: Vec<ReleaseEntry>) -> Result<Vec<Release>> {
+    let mut sorted_entries = entries;
+    sorted_entries.sort();
 
     let mut terminal = crate::tui::setup_terminal()
         .map_err(|e| anyhow::anyhow!("Failed to setup terminal: {}", e))?;
@@ -52,7 +61,7 @@ pub async fn run_review_tui(releases: Vec<Release>) -> Result<Vec<String>> {
     })
     .detach();
 
-    let mut app = crate::app::App::new(&sorted_releases, ui_tx);
+    let mut app = crate::app::App::new(&sorted_entries, ui_tx);
     let res = block_on(async {
         let executor = Executor::new();
         executor
@@ -67,8 +76,8 @@ pub async fn run_review_tui(releases: Vec<Release>) -> Result<Vec<String>> {
         Ok(true) => {
             let selected = app
                 .get_selected_releases()
-                .iter()
-                .map(|e| e.to_string())
+                .into_iter()
+                .cloned()
                 .collect::<Vec<_>>();
             Ok(selected)
         }
@@ -77,7 +86,7 @@ pub async fn run_review_tui(releases: Vec<Release>) -> Result<Vec<String>> {
     }
 }
 
-pub fn parse_stdin() -> Result<Vec<Release>> {
+pub fn parse_stdin() -> Result<Vec<ReleaseEntry>> {
     let mut input = String::new();
     io::stdin().read_to_string(&mut input)?;
     Ok(serde_json::from_str(&input)?)
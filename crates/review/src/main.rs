@@ -6,13 +6,14 @@ mod args;
 use crate::args::Args;
 
 fn main() -> Result<()> {
-    Args::parse();
+    let args = Args::parse();
+    args.http.init();
 
     let releases = deputui_review::parse_stdin()?;
 
-    let selected_packages = smol::block_on(deputui_review::run_review_tui(releases))?;
+    let selected_releases = smol::block_on(deputui_review::run_review_tui(releases))?;
 
-    println!("{}", selected_packages.join(" "));
+    println!("{}", args.output.format(&selected_releases));
 
     Ok(())
 }
@@ -32,7 +32,7 @@ impl<T> Widget for MultiSelectView<'_, T> {
                     no_indicator()
                 };
 
-                create_option_item(&select_option.label, select_option.selected, indicator)
+                create_option_item(select_option, indicator)
             })
             .map(ListItem::new)
             .collect();
@@ -70,7 +70,10 @@ impl<T> MultiSelect<T> {
     }
 
     pub fn toggle(&mut self) {
-        self.options[self.cursor].selected = !self.options[self.cursor].selected;
+        let option = &mut self.options[self.cursor];
+        if !option.disabled {
+            option.selected = !option.selected;
+        }
     }
 
     pub fn selected_values(&self) -> Vec<&T> {
@@ -88,18 +91,39 @@ impl<T> MultiSelect<T> {
 
 pub struct SelectOption<T> {
     label: String,
+    badge: Option<Span<'static>>,
     value: T,
     selected: bool,
+    disabled: bool,
 }
 
 impl<T> SelectOption<T> {
     pub fn new(label: String, value: T) -> SelectOption<T> {
         SelectOption {
             label,
+            badge: None,
             value,
             selected: false,
+            disabled: false,
         }
     }
+
+    pub fn with_selected(mut self, selected: bool) -> SelectOption<T> {
+        self.selected = selected;
+        self
+    }
+
+    /// Disabled options cannot be selected, e.g. because there is nothing to select.
+    pub fn with_disabled(mut self, disabled: bool) -> SelectOption<T> {
+        self.disabled = disabled;
+        self
+    }
+
+    /// A short tag shown after the label, e.g. to categorize the option.
+    pub fn with_badge(mut self, badge: Option<Span<'static>>) -> SelectOption<T> {
+        self.badge = badge;
+        self
+    }
 }
 
 fn indicator(focused: bool) -> Span<'static> {
@@ -116,15 +140,23 @@ fn no_indicator() -> Span<'static> {
     Span::raw(" ")
 }
 
-fn create_option_item<'a>(label: &'a str, selected: bool, indicator: Span<'a>) -> Line<'a> {
-    Line::from(vec![
+fn create_option_item<'a, T>(option: &'a SelectOption<T>, indicator: Span<'a>) -> Line<'a> {
+    let mut spans = vec![
         indicator,
         Span::styled("[", Style::default().fg(Color::DarkGray)),
-        Span::styled(
-            if selected { "x" } else { " " },
-            Style::default().fg(Color::Green),
-        ),
+        match (option.disabled, option.selected) {
+            (true, _) => Span::styled("!", Style::default().fg(Color::Red)),
+            (false, true) => Span::styled("x", Style::default().fg(Color::Green)),
+            (false, false) => Span::raw(" "),
+        },
         Span::styled("]", Style::default().fg(Color::DarkGray)),
-        Span::raw(format!(" {: <25}", label)),
-    ])
+        Span::raw(format!(" {: <25}", option.label)),
+    ];
+
+    if let Some(badge) = &option.badge {
+        spans.push(Span::raw(" "));
+        spans.push(badge.clone());
+    }
+
+    Line::from(spans)
 }
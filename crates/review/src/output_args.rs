@@ -0,0 +1,103 @@
+use clap::Args;
+use common::release::Release;
+use std::collections::BTreeMap;
+
+/// Flags that control how the selected releases are printed.
+#[derive(Args, Debug, Clone)]
+pub struct OutputArgs {
+    /// Print a `pnpm --filter <project> update …` command per workspace project instead of `package@version` identifiers
+    #[arg(long)]
+    pub workspace_commands: bool,
+}
+
+impl OutputArgs {
+    pub fn format(&self, selected: &[Release]) -> String {
+        match self.workspace_commands {
+            true => workspace_commands(selected).join("\n"),
+            false => identifiers(selected).join(" "),
+        }
+    }
+}
+
+fn identifiers<'a>(releases: impl IntoIterator<Item = &'a Release>) -> Vec<String> {
+    releases.into_iter().map(|r| r.to_string()).collect()
+}
+
+/// Releases without known dependents are updated in the project root.
+fn workspace_commands(selected: &[Release]) -> Vec<String> {
+    let mut releases_by_project: BTreeMap<Option<&str>, Vec<&Release>> = BTreeMap::new();
+    for release in selected {
+        if release.dependents.is_empty() {
+            releases_by_project.entry(None).or_default().push(release);
+        }
+        for dependent in &release.dependents {
+            releases_by_project
+                .entry(Some(dependent))
+                .or_default()
+                .push(release);
+        }
+    }
+
+    releases_by_project
+        .into_iter()
+        .map(|(project, releases)| {
+            let identifiers = identifiers(releases).join(" ");
+            match project {
+                Some(project) => format!("pnpm --filter {} update {}", project, identifiers),
+                None => format!("pnpm update {}", identifiers),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::release::UpdateKind;
+
+    fn release(package: &str, semver: &str, dependents: &[&str]) -> Release {
+        Release {
+            package: package.to_string(),
+            semver: semver.to_string(),
+            repository_url: Some("https://github.com/foo/foo".to_string()),
+            kind: UpdateKind::Minor,
+            dependents: dependents.iter().map(|d| d.to_string()).collect(),
+            dependency_type: None,
+            directory: None,
+            git_head: None,
+            tarball_url: None,
+        }
+    }
+
+    #[test]
+    fn test_format_identifiers() {
+        let selected = [
+            release("foo", "1.1.0", &["a"]),
+            release("bar", "2.1.0", &[]),
+        ];
+        let output_args = OutputArgs {
+            workspace_commands: false,
+        };
+
+        assert_eq!(output_args.format(&selected), "foo@1.1.0 bar@2.1.0");
+    }
+
+    #[test]
+    fn test_format_workspace_commands() {
+        let selected = [
+            release("foo", "1.1.0", &["a", "b"]),
+            release("bar", "2.1.0", &["b"]),
+            release("baz", "3.1.0", &[]),
+        ];
+        let output_args = OutputArgs {
+            workspace_commands: true,
+        };
+
+        assert_eq!(
+            output_args.format(&selected),
+            "pnpm update baz@3.1.0\n\
+             pnpm --filter a update foo@1.1.0\n\
+             pnpm --filter b update foo@1.1.0 bar@2.1.0"
+        );
+    }
+}
@@ -1,5 +1,12 @@
-use crate::github::GitHubRepo;
-use anyhow::{anyhow, bail, Result};
+use crate::{
+    changelog,
+    gitea::{self, GiteaRepo},
+    github::GitHubRepo,
+    gitlab::{self, GitLabProject},
+    tags::ReleaseHost,
+    tarball,
+};
+use anyhow::{bail, Result};
 use common::release::Release;
 use std::ops::Deref;
 use url::Url;
@@ -9,23 +16,86 @@ pub struct ReleaseExt<'a>(pub &'a Release);
 
 impl<'a> ReleaseExt<'a> {
     pub async fn fetch_release_notes(&self) -> Result<String> {
-        if Url::parse(&self.0.repository_url).is_err() {
+        match self.fetch_repository_release_notes().await {
+            Ok(notes) => Ok(notes),
+            // The published package may ship a changelog itself, e.g. when the repository is private or gone
+            Err(e) => match self.fetch_tarball_changelog_section().await {
+                Some(notes) => Ok(notes),
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn fetch_repository_release_notes(&self) -> Result<String> {
+        let Some(repository_url) = &self.0.repository_url else {
+            bail!("No repository known for {}", self.0.package);
+        };
+
+        if Url::parse(repository_url).is_err() {
             bail!("Invalid repository URL".to_string());
         }
 
-        if !is_github_url(&self.0.repository_url) {
-            bail!("Only GitHub repositories are supported for release notes".to_string());
+        if let Ok(github_repo) = GitHubRepo::from_github_url(repository_url) {
+            return match github_repo
+                .fetch_release_by_version(&self.0.package, &self.0.semver)
+                .await
+            {
+                Ok(release) => Ok(release.body.unwrap_or("Empty release notes".into())),
+                // Many packages never publish releases on GitHub, but keep a changelog
+                Err(e) => match self.fetch_changelog_section(&github_repo).await {
+                    Some(notes) => Ok(notes),
+                    None => Err(e.context("Failed to fetch release notes")),
+                },
+            };
+        }
+
+        if let Ok(gitlab_project) =
+            GitLabProject::from_gitlab_url(repository_url, &gitlab::instances())
+        {
+            return match gitlab_project
+                .fetch_release_by_version(&self.0.package, &self.0.semver)
+                .await
+            {
+                Ok(release) => Ok(release.description.unwrap_or("Empty release notes".into())),
+                Err(e) => Err(e.context("Failed to fetch release notes")),
+            };
         }
 
-        match GitHubRepo::from_github_url(&self.0.repository_url) {
-            Ok(github_repo) => match github_repo.fetch_release_by_version(&self.0.semver).await {
+        if let Ok(gitea_repo) = GiteaRepo::from_gitea_url(repository_url, &gitea::instances()) {
+            return match gitea_repo
+                .fetch_release_by_version(&self.0.package, &self.0.semver)
+                .await
+            {
                 Ok(release) => Ok(release.body.unwrap_or("Empty release notes".into())),
-                Err(e) => Err(anyhow!("Failed to fetch release notes: {}", e)),
-            },
-            Err(_) => {
-                panic!("Invalid GitHub URL; this should have been caught earlier")
-            }
+                Err(e) => Err(e.context("Failed to fetch release notes")),
+            };
         }
+
+        bail!(
+            "Only GitHub, GitLab and Gitea repositories are supported for release notes"
+                .to_string()
+        );
+    }
+
+    /// The section of this version in the repository's changelog, if there is one.
+    async fn fetch_changelog_section(&self, github_repo: &GitHubRepo) -> Option<String> {
+        let (path, content) = github_repo
+            .fetch_changelog(self.0.directory.as_deref(), self.0.git_head.as_deref())
+            .await
+            .ok()??;
+        let section = changelog::version_section(&content, &self.0.semver)?;
+        Some(format!("*From {}*\n\n{}", path, section))
+    }
+
+    /// The section of this version in the changelog of the published package, if there is one.
+    async fn fetch_tarball_changelog_section(&self) -> Option<String> {
+        let tarball_url = self.0.tarball_url.as_deref()?;
+        let (file_name, content) = tarball::fetch_changelog(tarball_url).await.ok()??;
+        let section = changelog::version_section(&content, &self.0.semver)?;
+        Some(format!(
+            "*From {} of the npm package*\n\n{}",
+            file_name, section
+        ))
     }
 }
 
@@ -33,13 +103,6 @@ impl<'a> Deref for ReleaseExt<'a> {
     type Target = Release;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-pub fn is_github_url(url_str: &str) -> bool {
-    match Url::parse(url_str) {
-        Ok(url) => url.host_str() == Some("github.com"),
-        _ => false,
+        self.0
     }
 }
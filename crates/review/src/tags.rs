@@ -0,0 +1,293 @@
+//! Finds the release of a version on hosts like GitHub and GitLab, where it's tagged in one of many ways, especially in monorepos.
+
+use anyhow::{bail, Result};
+
+use common::async_h1_client::cache::NotCached;
+
+/// How many of the latest releases to look through when no tag matches the version.
+pub const RELEASES_PER_PAGE: usize = 100;
+
+/// A host of releases by tag, e.g. a GitHub repository.
+pub trait ReleaseHost {
+    type Release: TaggedRelease;
+
+    /// The release of `tag`, or `None` if there is none.
+    async fn fetch_release(&self, tag: &str) -> Result<Option<Self::Release>>;
+
+    /// The latest releases.
+    async fn fetch_releases(&self) -> Result<Vec<Self::Release>>;
+
+    /// The release of `version` of `package`, see [`fetch_release_by_version`].
+    async fn fetch_release_by_version(&self, package: &str, version: &str) -> Result<Self::Release>
+    where
+        Self: Sized,
+    {
+        fetch_release_by_version(self, package, version).await
+    }
+}
+
+pub trait TaggedRelease {
+    fn tag(&self) -> &str;
+
+    fn title(&self) -> Option<&str>;
+
+    /// The tag and the title
+    fn labels(&self) -> impl Iterator<Item = &str> {
+        [Some(self.tag()), self.title()].into_iter().flatten()
+    }
+}
+
+/// The release of `version` of `package` on `host`, looking through the latest releases before trying the common tags one by one. The listing is one request, and usually cached for all versions of the repository, where each tag costs a request.
+pub async fn fetch_release_by_version<H: ReleaseHost>(
+    host: &H,
+    package: &str,
+    version: &str,
+) -> Result<H::Release> {
+    let tags = tag_candidates(package, version);
+
+    // Offline, a release that isn't cached may still exist, so tell that apart from one that doesn't
+    let mut not_cached = None;
+    let releases = match host.fetch_releases().await {
+        Ok(releases) => releases,
+        Err(e) if NotCached::is_cause_of(&e) => {
+            not_cached = Some(e);
+            Vec::new()
+        }
+        Err(e) => return Err(e),
+    };
+    // Without any release, there is no tag to try
+    let has_releases = !releases.is_empty() || not_cached.is_some();
+
+    let (release, fallback) = find_release(releases, &tags, package, version);
+    if let Some(release) = release {
+        return Ok(release);
+    }
+
+    // Older releases aren't listed
+    if has_releases {
+        for tag in &tags {
+            match host.fetch_release(tag).await {
+                Ok(Some(release)) => return Ok(release),
+                Ok(None) => continue, // Try next tag format
+                Err(e) if NotCached::is_cause_of(&e) => not_cached = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    if let Some(release) = fallback {
+        return Ok(release);
+    }
+    match not_cached {
+        Some(e) => Err(e),
+        None => bail!("No release found"),
+    }
+}
+
+/// The tags that `version` of `package` is commonly released under, most common first.
+fn tag_candidates(package: &str, version: &str) -> Vec<String> {
+    let name = unscoped_name(package);
+    let candidates = [
+        version.to_string(),
+        format!("v{}", version),
+        format!("{}@{}", package, version),
+        format!("{}@{}", name, version),
+        format!("{}@v{}", name, version),
+        format!("{}-v{}", name, version),
+        format!("{}-{}", name, version),
+        format!("{}/v{}", name, version),
+    ];
+
+    let mut tags: Vec<String> = Vec::new();
+    for candidate in candidates {
+        if !tags.contains(&candidate) {
+            tags.push(candidate);
+        }
+    }
+    tags
+}
+
+/// E.g. `core` for `@babel/core`
+fn unscoped_name(package: &str) -> &str {
+    match package.strip_prefix('@') {
+        Some(scoped) => scoped.split_once('/').map_or(package, |(_, name)| name),
+        None => package,
+    }
+}
+
+/// The release tagged with one of `tags`, or else one whose tag or title mentions `version` and `package`. Releases mentioning only `version` are likely those of other packages of a monorepo, so the one of those is returned as a fallback.
+fn find_release<R: TaggedRelease>(
+    mut releases: Vec<R>,
+    tags: &[String],
+    package: &str,
+    version: &str,
+) -> (Option<R>, Option<R>) {
+    if let Some(index) = tags
+        .iter()
+        .find_map(|tag| releases.iter().position(|release| release.tag() == tag))
+    {
+        return (Some(releases.swap_remove(index)), None);
+    }
+
+    let name = unscoped_name(package);
+    let (of_package, of_others): (Vec<_>, Vec<_>) = releases
+        .into_iter()
+        .filter(|release| {
+            release
+                .labels()
+                .any(|label| mentions_version(label, version))
+        })
+        .partition(|release| release.labels().any(|label| label.contains(name)));
+
+    (of_package.into_iter().next(), of_others.into_iter().next())
+}
+
+/// Whether `label` contains `version` on its own, e.g. `core-v1.2.0` but neither `11.2.0` nor `1.2.0-beta.1` for `1.2.0`.
+fn mentions_version(label: &str, version: &str) -> bool {
+    label.match_indices(version).any(|(start, _)| {
+        let before = label[..start].chars().next_back();
+        let after = label[start + version.len()..].chars().next();
+        // A `v` prefix belongs to the tag convention rather than to another word
+        let before_ok = before.is_none_or(|c| c == 'v' || !(c.is_ascii_alphanumeric() || c == '.'));
+        let after_ok =
+            after.is_none_or(|c| !(c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+'));
+        before_ok && after_ok
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct FakeRelease(&'static str, &'static str);
+
+    impl TaggedRelease for FakeRelease {
+        fn tag(&self) -> &str {
+            self.0
+        }
+
+        fn title(&self) -> Option<&str> {
+            Some(self.1)
+        }
+    }
+
+    /// Releases by tag and title, of which only the first `listed` are listed. Records the tags it's asked for, and `*` for listings.
+    struct FakeHost {
+        releases: &'static [(&'static str, &'static str)],
+        listed: usize,
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl FakeHost {
+        fn new(releases: &'static [(&'static str, &'static str)], listed: usize) -> FakeHost {
+            FakeHost {
+                releases,
+                listed,
+                requests: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn fetch(&self, package: &str, version: &str) -> Option<&'static str> {
+            self.requests.borrow_mut().clear();
+            smol::block_on(fetch_release_by_version(self, package, version))
+                .ok()
+                .map(|release| release.0)
+        }
+    }
+
+    impl ReleaseHost for FakeHost {
+        type Release = FakeRelease;
+
+        async fn fetch_release(&self, tag: &str) -> Result<Option<FakeRelease>> {
+            self.requests.borrow_mut().push(tag.to_string());
+            Ok(self
+                .releases
+                .iter()
+                .find(|(t, _)| *t == tag)
+                .map(|(tag, title)| FakeRelease(tag, title)))
+        }
+
+        async fn fetch_releases(&self) -> Result<Vec<FakeRelease>> {
+            self.requests.borrow_mut().push("*".to_string());
+            Ok(self
+                .releases
+                .iter()
+                .take(self.listed)
+                .map(|(tag, title)| FakeRelease(tag, title))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_listed_releases_cost_one_request() {
+        let host = FakeHost::new(&[("core@1.0.0", "core 1.0.0"), ("v1.0.0", "1.0.0")], 2);
+
+        // The most common tag wins, as if they were tried one by one
+        assert_eq!(host.fetch("@acme/core", "1.0.0"), Some("v1.0.0"));
+        assert_eq!(*host.requests.borrow(), ["*"]);
+    }
+
+    #[test]
+    fn test_unlisted_releases_are_looked_up_by_tag() {
+        let host = FakeHost::new(&[("v2.0.0", "2.0.0"), ("v1.0.0", "1.0.0")], 1);
+
+        assert_eq!(host.fetch("foo", "1.0.0"), Some("v1.0.0"));
+        assert_eq!(*host.requests.borrow(), ["*", "1.0.0", "v1.0.0"]);
+    }
+
+    #[test]
+    fn test_tags_are_not_tried_without_releases() {
+        let host = FakeHost::new(&[], 0);
+
+        assert_eq!(host.fetch("foo", "1.0.0"), None);
+        assert_eq!(*host.requests.borrow(), ["*"]);
+    }
+
+    #[test]
+    fn test_releases_of_other_packages_are_the_last_resort() {
+        let host = FakeHost::new(&[("ui-v3.0.0", "ui 3.0.0"), ("api-v3.0.0", "api 3.0.0")], 1);
+
+        assert_eq!(host.fetch("@acme/api", "3.0.0"), Some("api-v3.0.0"));
+        assert_eq!(host.fetch("@acme/db", "3.0.0"), Some("ui-v3.0.0"));
+        assert_eq!(
+            host.requests.borrow().len(),
+            1 + tag_candidates("@acme/db", "3.0.0").len()
+        );
+    }
+
+    #[test]
+    fn test_tag_candidates() {
+        assert_eq!(
+            tag_candidates("@babel/core", "7.24.0"),
+            [
+                "7.24.0",
+                "v7.24.0",
+                "@babel/core@7.24.0",
+                "core@7.24.0",
+                "core@v7.24.0",
+                "core-v7.24.0",
+                "core-7.24.0",
+                "core/v7.24.0",
+            ]
+        );
+        assert_eq!(tag_candidates("react", "19.0.0")[2], "react@19.0.0");
+        assert_eq!(tag_candidates("react", "19.0.0").len(), 7);
+    }
+
+    #[test]
+    fn test_mentions_version() {
+        for label in [
+            "1.2.0",
+            "v1.2.0",
+            "core-v1.2.0",
+            "@scope/core@1.2.0",
+            "Release 1.2.0 (2024-01-01)",
+        ] {
+            assert!(mentions_version(label, "1.2.0"), "{}", label);
+        }
+        for label in ["11.2.0", "1.2.0-beta.1", "1.2.0.1", "v1.2.01"] {
+            assert!(!mentions_version(label, "1.2.0"), "{}", label);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Reads changelogs from the tarball a package was published as, which works for private, deleted and non-GitHub repositories alike, and costs no GitHub quota.
+
+use std::io::{self, Read};
+
+use anyhow::{bail, Error, Result};
+use flate2::read::GzDecoder;
+use http_types::{Method, Request};
+use smol::io::AsyncReadExt;
+use url::Url;
+
+use common::{
+    async_h1_client::{
+        self,
+        cache::{self, NotCached},
+        RetryPolicy,
+    },
+    npmrc,
+};
+
+use crate::changelog;
+
+/// How much a tarball may make us read, so that a huge one cannot exhaust memory. Exceeding any of these is an error rather than a truncated changelog.
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    /// Of the download
+    tarball: u64,
+    /// Of the whole unpacked archive, which has to be read through
+    unpacked: u64,
+    changelog: u64,
+}
+
+const LIMITS: Limits = Limits {
+    tarball: 64 * 1024 * 1024,
+    unpacked: 512 * 1024 * 1024,
+    changelog: 16 * 1024 * 1024,
+};
+
+/// The name and content of the changelog that the tarball at `url` ships, if any.
+pub async fn fetch_changelog(url: &str) -> Result<Option<(String, String)>> {
+    fetch_changelog_within(url, LIMITS).await
+}
+
+async fn fetch_changelog_within(url: &str, limits: Limits) -> Result<Option<(String, String)>> {
+    let url = Url::parse(url)?;
+    // Tarballs are megabytes, read once per release, so they're not worth a place in the cache
+    if cache::get().is_offline() {
+        return Err(Error::new(NotCached {
+            url: url.to_string(),
+        }));
+    }
+
+    let mut req = Request::new(Method::Get, url.clone());
+    // Private registries want their token for tarballs as well
+    if let Some(token) = npmrc::get().auth_token_for(url.as_str()) {
+        req.insert_header("Authorization", format!("Bearer {}", token));
+    }
+
+    let resp = async_h1_client::fetch_with_retries(req, &RetryPolicy::default()).await?;
+
+    if resp.status() != 200 {
+        bail!("NPM registry error for {}: {}", url, resp.status());
+    }
+    if resp.len().is_some_and(|len| len as u64 > limits.tarball) {
+        bail!("Tarball {} is larger than {} bytes", url, limits.tarball);
+    }
+
+    let mut body = Vec::new();
+    resp.take(limits.tarball + 1).read_to_end(&mut body).await?;
+    if body.len() as u64 > limits.tarball {
+        bail!("Tarball {} is larger than {} bytes", url, limits.tarball);
+    }
+
+    smol::unblock(move || find_changelog(&body, limits)).await
+}
+
+/// Looks for a changelog in the top directory of a gzipped tarball, usually `package/`, preferring the more common names.
+fn find_changelog(tarball: &[u8], limits: Limits) -> Result<Option<(String, String)>> {
+    let mut found: Option<(usize, String, String)> = None;
+
+    let unpacked = Limited {
+        inner: GzDecoder::new(tarball),
+        remaining: limits.unpacked,
+    };
+    let mut archive = tar::Archive::new(unpacked);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?;
+        let mut components = path.components();
+        let (Some(_), Some(file_name), None) =
+            (components.next(), components.next(), components.next())
+        else {
+            continue;
+        };
+        let file_name = file_name.as_os_str().to_string_lossy().into_owned();
+
+        let Some(rank) = changelog::FILE_NAMES
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(&file_name))
+        else {
+            continue;
+        };
+        if found.as_ref().is_some_and(|(best, _, _)| *best <= rank) {
+            continue;
+        }
+
+        let mut content = Vec::new();
+        entry.take(limits.changelog + 1).read_to_end(&mut content)?;
+        if content.len() as u64 > limits.changelog {
+            bail!("{} is larger than {} bytes", file_name, limits.changelog);
+        }
+        found = Some((
+            rank,
+            file_name,
+            String::from_utf8_lossy(&content).into_owned(),
+        ));
+    }
+
+    Ok(found.map(|(_, file_name, content)| (file_name, content)))
+}
+
+/// Fails reads past a limit, where [`Read::take`] would end the stream as if it were complete.
+struct Limited<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.remaining = self
+            .remaining
+            .checked_sub(read as u64)
+            .ok_or_else(|| io::Error::other("The unpacked tarball is too large"))?;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::test_server::{TestResponse, TestServer};
+    use flate2::{write::GzEncoder, Compression};
+
+    fn tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_find_changelog() {
+        let tarball = tarball(&[
+            ("package/package.json", "{}"),
+            ("package/docs/CHANGELOG.md", "# Docs"),
+            ("package/History.md", "# History"),
+            ("package/CHANGELOG.md", "# Changelog"),
+        ]);
+
+        assert_eq!(
+            find_changelog(&tarball, LIMITS).unwrap(),
+            Some(("CHANGELOG.md".to_string(), "# Changelog".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_changelog_in_any_top_directory() {
+        let tarball = tarball(&[("node/History.md", "# History")]);
+
+        assert_eq!(
+            find_changelog(&tarball, LIMITS).unwrap(),
+            Some(("History.md".to_string(), "# History".to_string()))
+        );
+        assert_eq!(
+            find_changelog(&self::tarball(&[("package/README.md", "")]), LIMITS).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fetch_changelog() {
+        let body = tarball(&[("package/CHANGELOG.md", "## 1.1.0\n\n- New\n")]);
+        let server = TestServer::start(move |req| match req.target.as_str() {
+            "/foo/-/foo-1.1.0.tgz" => TestResponse::new(200, body.clone()),
+            _ => TestResponse::new(404, "{}"),
+        });
+
+        let (file_name, content) =
+            smol::block_on(fetch_changelog(&server.url("/foo/-/foo-1.1.0.tgz")))
+                .unwrap()
+                .unwrap();
+        assert_eq!(file_name, "CHANGELOG.md");
+        assert_eq!(
+            changelog::version_section(&content, "1.1.0").as_deref(),
+            Some("- New")
+        );
+
+        assert!(smol::block_on(fetch_changelog(&server.url("/foo/-/foo-9.9.9.tgz"))).is_err());
+    }
+
+    #[test]
+    fn test_limits_are_errors() {
+        let tarball = tarball(&[("package/CHANGELOG.md", "# Changelog")]);
+
+        let small_changelog = Limits {
+            changelog: 4,
+            ..LIMITS
+        };
+        assert!(find_changelog(&tarball, small_changelog).is_err());
+
+        let small_unpacked = Limits {
+            unpacked: 100,
+            ..LIMITS
+        };
+        assert!(find_changelog(&tarball, small_unpacked).is_err());
+
+        let server = TestServer::start(move |_| TestResponse::new(200, tarball.clone()));
+        let small_tarball = Limits {
+            tarball: 10,
+            ..LIMITS
+        };
+        let error = smol::block_on(fetch_changelog_within(
+            &server.url("/foo/-/foo-1.1.0.tgz"),
+            small_tarball,
+        ))
+        .unwrap_err();
+        assert!(error.to_string().contains("larger than 10 bytes"));
+    }
+}
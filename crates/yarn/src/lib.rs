@@ -0,0 +1,3 @@
+pub mod yarn;
+
+pub use crate::yarn::{parse, parse_input, YarnBerryOutdatedPackage, YarnClassicEvent};
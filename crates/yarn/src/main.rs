@@ -0,0 +1,32 @@
+use anyhow::Result;
+use clap::Parser;
+use common::release::ReleaseEntry;
+use smol::block_on;
+
+mod args;
+
+use deputui_pnpm::fetch_all_releases;
+use deputui_yarn::parse_input;
+
+use crate::args::Args;
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    args.http.init();
+
+    let parsed = parse_input()?;
+    let filter = args.fetch.release_filter();
+    filter.check_dependency_types(&parsed)?;
+
+    let mut releases = block_on(fetch_all_releases(parsed, &filter, args.fetch.concurrency));
+    releases.sort();
+
+    for error in releases.iter().filter_map(ReleaseEntry::as_error) {
+        eprintln!("Failed to fetch releases of {}", error);
+    }
+
+    let json_output = serde_json::to_string(&releases)?;
+    println!("{}", json_output);
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use anyhow::{anyhow, Context as _, Result};
+use common::{outdated::OutdatedPackage, release::DependencyType};
+use serde::Deserialize;
+use std::io::{self, Read};
+
+/// A line of `yarn outdated --json` output of Yarn classic (v1), which prints one JSON event per line.
+#[derive(Debug, Deserialize)]
+pub struct YarnClassicEvent {
+    pub r#type: String,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YarnClassicTable {
+    pub head: Vec<String>,
+    pub body: Vec<Vec<String>>,
+}
+
+/// An entry of `yarn outdated --json` output of Yarn berry (v2+), which needs the `yarn-plugin-outdated` plugin.
+#[derive(Debug, Deserialize)]
+pub struct YarnBerryOutdatedPackage {
+    pub name: String,
+    pub current: String,
+    pub latest: String,
+    pub workspace: Option<String>,
+    /// The `package.json` section, e.g. `devDependencies`
+    pub r#type: Option<String>,
+}
+
+pub fn parse_input() -> Result<Vec<OutdatedPackage>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    parse(&input)
+}
+
+/// Parses the output of `yarn outdated --json` of both Yarn classic and Yarn berry.
+pub fn parse(input: &str) -> Result<Vec<OutdatedPackage>> {
+    match input.trim_start().starts_with('[') {
+        true => parse_berry(input),
+        false => parse_classic(input),
+    }
+}
+
+fn parse_berry(input: &str) -> Result<Vec<OutdatedPackage>> {
+    let entries: Vec<YarnBerryOutdatedPackage> = serde_json::from_str(input)?;
+
+    let mut packages = Vec::new();
+    for entry in entries {
+        OutdatedPackage {
+            name: entry.name,
+            current: entry.current,
+            latest: entry.latest,
+            dependents: entry.workspace.into_iter().collect(),
+            dependency_type: entry
+                .r#type
+                .as_deref()
+                .and_then(DependencyType::from_section),
+        }
+        .merge_into(&mut packages);
+    }
+
+    Ok(packages)
+}
+
+fn parse_classic(input: &str) -> Result<Vec<OutdatedPackage>> {
+    let mut packages = Vec::new();
+
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let event: YarnClassicEvent = serde_json::from_str(line)?;
+        if event.r#type != "table" {
+            continue;
+        }
+        let table: YarnClassicTable = serde_json::from_value(event.data)?;
+
+        // Workspaces add a "Workspace" column, so look up the columns by name
+        let column = |name: &str| {
+            table
+                .head
+                .iter()
+                .position(|head| head == name)
+                .ok_or_else(|| anyhow!("Missing column in yarn output: {}", name))
+        };
+        let (name, current, latest) = (column("Package")?, column("Current")?, column("Latest")?);
+
+        let workspace = column("Workspace").ok();
+        let package_type = column("Package Type").ok();
+
+        for row in &table.body {
+            let cell = |i: usize| row.get(i).cloned().context("Incomplete row in yarn output");
+
+            OutdatedPackage {
+                name: cell(name)?,
+                current: cell(current)?,
+                latest: cell(latest)?,
+                dependents: workspace.map(cell).transpose()?.into_iter().collect(),
+                dependency_type: package_type
+                    .and_then(|i| row.get(i))
+                    .and_then(|section| DependencyType::from_section(section)),
+            }
+            .merge_into(&mut packages);
+        }
+    }
+
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outdated_package(
+        name: &str,
+        current: &str,
+        latest: &str,
+        dependents: &[&str],
+        dependency_type: DependencyType,
+    ) -> OutdatedPackage {
+        OutdatedPackage {
+            name: name.to_string(),
+            current: current.to_string(),
+            latest: latest.to_string(),
+            dependents: dependents.iter().map(|d| d.to_string()).collect(),
+            dependency_type: Some(dependency_type),
+        }
+    }
+
+    #[test]
+    fn test_parse_classic() {
+        let input = concat!(
+            r#"{"type":"info","data":"Color legend: ..."}"#,
+            "\n",
+            r#"{"type":"table","data":{"head":["Package","Current","Wanted","Latest","Package Type","URL"],"body":[["foo","1.0.0","1.0.0","1.1.0","dependencies","https://foo.bar"],["bar","2.0.0","2.0.1","2.3.0","devDependencies","https://bar.baz"]]}}"#,
+            "\n",
+        );
+
+        let packages = parse(input).unwrap();
+
+        assert_eq!(
+            packages,
+            vec![
+                outdated_package("foo", "1.0.0", "1.1.0", &[], DependencyType::Prod),
+                outdated_package("bar", "2.0.0", "2.3.0", &[], DependencyType::Dev),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_classic_workspaces() {
+        let input = r#"{"type":"table","data":{"head":["Package","Current","Wanted","Latest","Workspace","Package Type","URL"],"body":[["foo","1.0.0","1.0.0","1.1.0","a","dependencies","https://foo.bar"],["foo","1.0.0","1.0.0","1.1.0","b","dependencies","https://foo.bar"]]}}"#;
+
+        let packages = parse(input).unwrap();
+
+        assert_eq!(
+            packages,
+            vec![outdated_package(
+                "foo",
+                "1.0.0",
+                "1.1.0",
+                &["a", "b"],
+                DependencyType::Prod
+            )]
+        );
+    }
+
+    #[test]
+    fn test_parse_berry() {
+        let input = r#"[
+            { "current": "1.0.0", "latest": "1.1.0", "name": "foo", "range": "^1.0.0", "type": "dependencies", "workspace": "a" },
+            { "current": "1.0.0", "latest": "1.1.0", "name": "foo", "range": "^1.0.0", "type": "dependencies", "workspace": "b" }
+        ]"#;
+
+        let packages = parse(input).unwrap();
+
+        assert_eq!(
+            packages,
+            vec![outdated_package(
+                "foo",
+                "1.0.0",
+                "1.1.0",
+                &["a", "b"],
+                DependencyType::Prod
+            )]
+        );
+    }
+}